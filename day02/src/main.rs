@@ -1,27 +1,25 @@
 use std::error::Error;
-use std::fs;
 
-use intcode::{Computer, ValueType};
+use intcode::symbolic::Solver;
+use intcode::{Computer, Program, ValueType};
 
 const ORIGINAL_OUTPUT: ValueType = 19_690_720;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let program = fs::read_to_string("input.txt")?;
-    let mut computer = Computer::new(program.trim())?;
+    let program = Program::from_file("input.txt")?;
+    let mut computer = Computer::from_program(&program);
 
-    computer.run_with_values(1, &[12, 2]);
+    computer.run_with_values(1, &[12, 2])?;
 
-    println!("Output: {}", computer.dma(0));
+    println!("Output: {}", computer.dma(0)?);
 
-    'outer: for noun in 0..=99 {
-        for verb in 0..=99 {
-            computer.run_with_values(1, &[noun, verb]);
+    let found = Solver::new(&program)
+        .variable(1, 0..=99)
+        .variable(2, 0..=99)
+        .solve(0, ORIGINAL_OUTPUT);
 
-            if *computer.dma(0) == ORIGINAL_OUTPUT {
-                println!("Original inputs: {}", noun * 100 + verb);
-                break 'outer;
-            }
-        }
+    if let Some(values) = found {
+        println!("Original inputs: {}", values[0] * 100 + values[1]);
     }
 
     Ok(())
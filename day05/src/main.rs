@@ -1,17 +1,15 @@
 use std::error::Error;
-use std::fs;
 
 use intcode::Computer;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let program = fs::read_to_string("input.txt")?;
-    let mut computer = Computer::new(program.trim())?;
+    let mut computer = Computer::from_path("input.txt")?;
     let (sender, receiver) = computer.get_io();
 
     println!("Output for input 1:");
 
     sender.send(1)?;
-    computer.run();
+    computer.run()?;
 
     for message in receiver.iter() {
         println!("{}", message);
@@ -24,7 +22,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     println!("Output for input 5:");
 
     sender.send(5)?;
-    computer.run();
+    computer.run()?;
 
     println!("{}", receiver.recv()?);
 
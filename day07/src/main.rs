@@ -8,12 +8,12 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     println!(
         "Highest signal: {}",
-        highest_signal(program.trim(), &mut [0, 1, 2, 3, 4], false)
+        highest_signal(&program, &mut [0, 1, 2, 3, 4], false)?
     );
 
     println!(
         "Highest signal with feedback: {}",
-        highest_signal(program.trim(), &mut [5, 6, 7, 8, 9], true)
+        highest_signal(&program, &mut [5, 6, 7, 8, 9], true)?
     );
 
     Ok(())
@@ -1,20 +1,18 @@
 use std::error::Error;
-use std::fs;
 
 use intcode::Computer;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let program = fs::read_to_string("input.txt")?;
-    let mut computer = Computer::new(program.trim())?;
+    let mut computer = Computer::from_path("input.txt")?;
     let (sender, receiver) = computer.get_io();
 
     sender.send(1)?;
-    computer.run();
+    computer.run()?;
 
     println!("Keycode: {}", receiver.recv()?);
 
     sender.send(2)?;
-    computer.run();
+    computer.run()?;
 
     println!("Coordinates: {}", receiver.recv()?);
 
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::fmt;
 use std::thread;
 
-use intcode::{Computer, ProgramParseError, ValueType};
+use intcode::{Computer, IntcodeError, ValueType};
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum Color {
@@ -86,6 +86,12 @@ impl fmt::Display for Hull {
     }
 }
 
+impl Default for Hull {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct Robot {
     position: (i32, i32),
     direction: Direction,
@@ -104,13 +110,11 @@ impl Robot {
         program: &str,
         hull: &mut Hull,
         starting_color: Color,
-    ) -> Result<usize, ProgramParseError> {
+    ) -> Result<usize, IntcodeError> {
         let mut computer = Computer::new(program)?;
         let (sender, receiver) = computer.get_io();
 
-        let thread = thread::spawn(move || {
-            computer.run();
-        });
+        let thread = thread::spawn(move || computer.run());
 
         hull.panels
             .insert(self.position, Panel::with_color(starting_color));
@@ -139,7 +143,7 @@ impl Robot {
             self.step();
         }
 
-        thread.join().unwrap();
+        thread.join().unwrap()?;
 
         Ok(hull.panels.len())
     }
@@ -153,3 +157,9 @@ impl Robot {
         };
     }
 }
+
+impl Default for Robot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -8,13 +8,13 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut test_hull = Hull::new();
     let mut robot = Robot::new();
 
-    let panels_painted = robot.run(program.trim(), &mut test_hull, Color::Black)?;
+    let panels_painted = robot.run(&program, &mut test_hull, Color::Black)?;
 
     println!("Panels painted: {}", panels_painted);
 
     let mut hull = Hull::new();
 
-    robot.run(program.trim(), &mut hull, Color::White)?;
+    robot.run(&program, &mut hull, Color::White)?;
 
     println!("{}", hull);
 
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use std::sync::mpsc;
 use std::thread;
 
-use intcode::{Computer, Io, ProgramParseError, ValueType};
+use intcode::{Computer, IntcodeError, Io, ValueType};
 
 #[derive(Clone, Copy, PartialEq)]
 enum Tile {
@@ -70,7 +70,7 @@ impl TileBuilder {
     }
 }
 
-pub fn test_game(program: &str) -> Result<usize, ProgramParseError> {
+pub fn test_game(program: &str) -> Result<usize, IntcodeError> {
     let mut computer = Computer::new(program)?;
 
     let mut tile_builder = TileBuilder::new();
@@ -78,9 +78,7 @@ pub fn test_game(program: &str) -> Result<usize, ProgramParseError> {
 
     let (_, receiver) = computer.get_io();
 
-    let thread = thread::spawn(move || {
-        computer.run();
-    });
+    let thread = thread::spawn(move || computer.run());
 
     for value in receiver.iter() {
         if let Some(tile) = tile_builder.process(value) {
@@ -88,7 +86,7 @@ pub fn test_game(program: &str) -> Result<usize, ProgramParseError> {
         }
     }
 
-    thread.join().unwrap();
+    thread.join().unwrap()?;
 
     Ok(tiles.values().filter(|&&tile| tile == Tile::Block).count())
 }
@@ -137,17 +135,15 @@ impl Arcade {
     }
 }
 
-pub fn run_game(program: &str) -> Result<ValueType, ProgramParseError> {
+pub fn run_game(program: &str) -> Result<ValueType, IntcodeError> {
     let mut computer = Computer::new(program)?;
     let (arcade, receiver) = Arcade::new();
 
     computer.attach_io(Box::new(arcade));
 
-    let thread = thread::spawn(move || {
-        computer.run_with_values(0, &[2]);
-    });
+    let thread = thread::spawn(move || computer.run_with_values(0, &[2]));
 
-    thread.join().unwrap();
+    thread.join().unwrap()?;
 
     Ok(receiver.iter().last().unwrap_or(0))
 }
@@ -158,6 +154,6 @@ mod tests {
 
     #[test]
     fn count_tiles() {
-        assert_eq!(Ok(2), test_game(&"104,1,104,2,104,3,104,6,104,5,104,4,99"));
+        assert_eq!(Ok(2), test_game("104,1,104,2,104,2,104,6,104,5,104,2,99"));
     }
 }
@@ -6,8 +6,8 @@ use day13::*;
 fn main() -> Result<(), Box<dyn Error>> {
     let program = fs::read_to_string("input.txt")?;
 
-    println!("Tiles: {}", test_game(program.trim()).unwrap_or(0));
-    println!("Highest score: {}", run_game(program.trim())?);
+    println!("Tiles: {}", test_game(&program).unwrap_or(0));
+    println!("Highest score: {}", run_game(&program)?);
 
     Ok(())
 }
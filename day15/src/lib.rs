@@ -1,8 +1,6 @@
 use std::collections::HashSet;
-use std::sync::mpsc;
-use std::thread;
 
-use intcode::{Computer, ProgramParseError, ValueType};
+use intcode::{Computer, ProgramParseError, State, ValueType};
 
 #[derive(Clone, Copy, PartialEq)]
 enum Command {
@@ -51,30 +49,27 @@ impl From<ValueType> for Status {
 }
 
 pub struct RemoteControl {
-    sender: mpsc::Sender<ValueType>,
-    receiver: mpsc::Receiver<ValueType>,
-    shutdown_button: mpsc::Sender<()>,
+    computer: Computer,
     explored: HashSet<(ValueType, ValueType)>,
 }
 
 impl RemoteControl {
     pub fn new(program: &str) -> Result<Self, ProgramParseError> {
-        let mut computer = Computer::new(program)?;
-        let (sender, receiver) = computer.get_io();
-        let shutdown_button = computer.shutdown_button();
-
-        thread::spawn(move || {
-            computer.run();
-        });
-
         Ok(Self {
-            sender,
-            receiver,
-            shutdown_button,
+            computer: Computer::new(program)?,
             explored: HashSet::new(),
         })
     }
 
+    fn send(&mut self, command: Command) -> Status {
+        self.computer.provide_input(command as ValueType);
+
+        match self.computer.run_until_output().unwrap() {
+            State::Output(status) => status.into(),
+            state => panic!("droid stopped responding: {:?}", state),
+        }
+    }
+
     fn explore_direction(
         &mut self,
         position: (ValueType, ValueType),
@@ -87,16 +82,13 @@ impl RemoteControl {
             return None;
         }
 
-        self.sender.send(command as ValueType).unwrap();
-
-        match self.receiver.recv().unwrap().into() {
+        match self.send(command) {
             Status::WallHit => None,
             Status::Moved => {
                 if let Some(result) = self.explore(position, depth + 1) {
                     Some(result)
                 } else {
-                    self.sender.send(command.reverse() as ValueType).unwrap();
-                    self.receiver.recv().unwrap();
+                    self.send(command.reverse());
 
                     None
                 }
@@ -131,15 +123,12 @@ impl RemoteControl {
             return depth;
         }
 
-        self.sender.send(command as ValueType).unwrap();
-
-        match self.receiver.recv().unwrap().into() {
+        match self.send(command) {
             Status::WallHit => depth,
             _ => {
                 let result = self.max_depth(position, depth + 1);
 
-                self.sender.send(command.reverse() as ValueType).unwrap();
-                self.receiver.recv().unwrap();
+                self.send(command.reverse());
 
                 result
             }
@@ -166,10 +155,3 @@ impl RemoteControl {
         self.max_depth((0, 0), 0)
     }
 }
-
-impl Drop for RemoteControl {
-    fn drop(&mut self) {
-        self.shutdown_button.send(()).unwrap();
-        self.sender.send(0).unwrap();
-    }
-}
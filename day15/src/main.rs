@@ -5,7 +5,7 @@ use day15::*;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let program = fs::read_to_string("input.txt")?;
-    let mut remote_control = RemoteControl::new(program.trim())?;
+    let mut remote_control = RemoteControl::new(&program)?;
 
     println!("Target depth: {}", remote_control.find_target());
     println!("Max depth: {}", remote_control.find_max_depth());
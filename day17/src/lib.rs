@@ -1,6 +1,7 @@
-use std::thread;
+use std::sync::{Arc, Mutex};
 
-use intcode::{Computer, ProgramParseError};
+use intcode::ascii::AsciiIo;
+use intcode::{Computer, IntcodeError};
 
 #[derive(Clone, Copy, PartialEq)]
 enum Tile {
@@ -24,18 +25,12 @@ struct Map {
 }
 
 impl Map {
-    fn new() -> Self {
+    fn new(lines: &[String]) -> Self {
         Self {
-            tiles: vec![vec![]],
-        }
-    }
-
-    fn append(&mut self, c: char) {
-        let height = self.tiles.len();
-
-        match c {
-            '\n' => self.tiles.push(vec![]),
-            _ => self.tiles[height - 1].push(Tile::from(c)),
+            tiles: lines
+                .iter()
+                .map(|line| line.chars().map(Tile::from).collect())
+                .collect(),
         }
     }
 
@@ -65,21 +60,14 @@ impl Map {
     }
 }
 
-pub fn calculate_alignment(program: &str) -> Result<usize, ProgramParseError> {
+pub fn calculate_alignment(program: &str) -> Result<usize, IntcodeError> {
     let mut computer = Computer::new(program)?;
-    let (_, receiver) = computer.get_io();
+    let io = Arc::new(Mutex::new(AsciiIo::new()));
 
-    let thread = thread::spawn(move || {
-        computer.run();
-    });
-
-    let mut map = Map::new();
-
-    for message in receiver {
-        map.append(char::from(message as u8));
-    }
+    computer.attach_io(Box::new(io.clone()));
+    computer.run()?;
 
-    thread.join().unwrap();
+    let map = Map::new(&io.lock().unwrap().take_lines());
 
     Ok(map.intersections())
 }
@@ -5,7 +5,7 @@ use day17::*;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let program = fs::read_to_string("input.txt")?;
-    let alignment = calculate_alignment(program.trim())?;
+    let alignment = calculate_alignment(&program)?;
 
     println!("Sum of alignment parameters: {}", alignment);
 
@@ -1,32 +1,23 @@
-use std::sync::mpsc;
-
-use intcode::{Computer, ProgramParseError, ValueType};
+use intcode::{Computer, ProgramParseError, State, ValueType};
 
 pub struct Beam {
     computer: Computer,
-    sender: mpsc::Sender<ValueType>,
-    receiver: mpsc::Receiver<ValueType>,
 }
 
 impl Beam {
     pub fn new(program: &str) -> Result<Self, ProgramParseError> {
-        let mut computer = Computer::new(program)?;
-        let (sender, receiver) = computer.get_io();
-
         Ok(Self {
-            computer,
-            sender,
-            receiver,
+            computer: Computer::new(program)?,
         })
     }
 
     fn position_affected(&mut self, x: ValueType, y: ValueType) -> bool {
-        self.sender.send(x).unwrap();
-        self.sender.send(y).unwrap();
+        self.computer.reset().unwrap();
 
-        self.computer.run();
+        self.computer.provide_input(x);
+        self.computer.provide_input(y);
 
-        self.receiver.recv().unwrap() == 1
+        self.computer.run_until_output().unwrap() == State::Output(1)
     }
 
     pub fn area_affected(&mut self, size: ValueType) -> ValueType {
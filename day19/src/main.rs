@@ -6,7 +6,7 @@ use day19::*;
 fn main() -> Result<(), Box<dyn Error>> {
     let program = fs::read_to_string("input.txt")?;
 
-    let mut beam = Beam::new(program.trim())?;
+    let mut beam = Beam::new(&program)?;
 
     println!("Area affected: {}", beam.area_affected(50));
 
@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+
+use super::{Io, ValueType};
+
+#[derive(Default)]
+pub struct AsciiIo {
+    input: VecDeque<ValueType>,
+    line: String,
+    lines: VecDeque<String>,
+    answers: Vec<ValueType>,
+    terminal: bool,
+}
+
+impl AsciiIo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn terminal() -> Self {
+        Self {
+            terminal: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn send_command(&mut self, command: &str) {
+        self.input
+            .extend(command.bytes().chain(Some(b'\n')).map(ValueType::from));
+    }
+
+    pub fn take_lines(&mut self) -> Vec<String> {
+        self.lines.drain(..).collect()
+    }
+
+    pub fn partial_line(&self) -> &str {
+        &self.line
+    }
+
+    pub fn answers(&self) -> &[ValueType] {
+        &self.answers
+    }
+
+    fn read_command(&mut self) -> Option<()> {
+        io::stdout().flush().ok()?;
+
+        let mut command = String::new();
+
+        match io::stdin().lock().read_line(&mut command) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => {
+                self.send_command(command.trim_end_matches(['\r', '\n']));
+
+                Some(())
+            }
+        }
+    }
+}
+
+impl Io for AsciiIo {
+    fn send(&mut self, value: ValueType) {
+        match value {
+            0..=127 => {
+                let c = char::from(value as u8);
+
+                if self.terminal {
+                    print!("{}", c);
+                }
+
+                if c == '\n' {
+                    self.lines.push_back(self.line.split_off(0));
+                } else {
+                    self.line.push(c);
+                }
+            }
+            _ => {
+                if self.terminal {
+                    println!("{}", value);
+                }
+
+                self.answers.push(value);
+            }
+        }
+    }
+
+    fn receive(&mut self) -> ValueType {
+        self.try_receive().unwrap_or(0)
+    }
+
+    fn try_receive(&mut self) -> Option<ValueType> {
+        if self.input.is_empty() && self.terminal {
+            self.read_command()?;
+        }
+
+        self.input.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Computer;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn lines_and_answers() {
+        let io = Arc::new(Mutex::new(AsciiIo::new()));
+        let mut computer = Computer::new("104,72,104,105,104,10,104,1000,104,63,99").unwrap();
+
+        computer.attach_io(Box::new(io.clone()));
+        computer.run().unwrap();
+
+        let mut io = io.lock().unwrap();
+
+        assert_eq!(vec!["Hi".to_string()], io.take_lines());
+        assert!(io.take_lines().is_empty());
+        assert_eq!("?", io.partial_line());
+        assert_eq!(&[1000], io.answers());
+    }
+
+    #[test]
+    fn commands() {
+        let io = Arc::new(Mutex::new(AsciiIo::new()));
+        let mut computer = Computer::new("3,100,4,100,1008,100,10,101,1006,101,0,99").unwrap();
+
+        io.lock().unwrap().send_command("ok");
+        computer.attach_io(Box::new(io.clone()));
+        computer.run().unwrap();
+
+        assert_eq!(vec!["ok".to_string()], io.lock().unwrap().take_lines());
+    }
+}
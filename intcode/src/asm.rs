@@ -0,0 +1,460 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use super::{Instruction, Opcode, ParameterMode, Program, ValueType};
+
+#[derive(Debug, PartialEq)]
+pub enum AssembleError {
+    UnknownMnemonic {
+        line: usize,
+        mnemonic: String,
+    },
+    InvalidOperand {
+        line: usize,
+        operand: String,
+    },
+    InvalidLabel {
+        line: usize,
+        label: String,
+    },
+    WrongOperandCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    DuplicateLabel {
+        line: usize,
+        label: String,
+    },
+    UndefinedLabel {
+        line: usize,
+        label: String,
+    },
+    AddressMismatch {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssembleError::UnknownMnemonic { line, mnemonic } => {
+                write!(f, "line {}: unknown mnemonic {}", line, mnemonic)
+            }
+            AssembleError::InvalidOperand { line, operand } => {
+                write!(f, "line {}: invalid operand {}", line, operand)
+            }
+            AssembleError::InvalidLabel { line, label } => {
+                write!(f, "line {}: invalid label {}", line, label)
+            }
+            AssembleError::WrongOperandCount {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {} operands, found {}",
+                line, expected, found
+            ),
+            AssembleError::DuplicateLabel { line, label } => {
+                write!(f, "line {}: duplicate label {}", line, label)
+            }
+            AssembleError::UndefinedLabel { line, label } => {
+                write!(f, "line {}: undefined label {}", line, label)
+            }
+            AssembleError::AddressMismatch {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected address {}, found {}",
+                line, expected, found
+            ),
+        }
+    }
+}
+
+impl Error for AssembleError {}
+
+enum Expression {
+    Number(ValueType),
+    Label(String, ValueType),
+}
+
+impl Expression {
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+
+        if let Ok(value) = s.parse() {
+            return Some(Expression::Number(value));
+        }
+
+        let (label, offset) = match s.rfind(['+', '-']) {
+            Some(i) if i > 0 => (s[..i].trim(), s[i..].replace(' ', "").parse().ok()?),
+            _ => (s, 0),
+        };
+
+        if is_identifier(label) {
+            Some(Expression::Label(label.to_string(), offset))
+        } else {
+            None
+        }
+    }
+
+    fn resolve(
+        &self,
+        labels: &HashMap<String, usize>,
+        line: usize,
+    ) -> Result<ValueType, AssembleError> {
+        match self {
+            Expression::Number(value) => Ok(*value),
+            Expression::Label(label, offset) => labels
+                .get(label)
+                .map(|&address| address as ValueType + offset)
+                .ok_or_else(|| AssembleError::UndefinedLabel {
+                    line,
+                    label: label.clone(),
+                }),
+        }
+    }
+}
+
+struct Operand {
+    mode: ParameterMode,
+    expression: Expression,
+}
+
+impl Operand {
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+
+        let (mode, expression) = if s.starts_with('[') && s.ends_with(']') {
+            (
+                ParameterMode::Position,
+                Expression::parse(&s[1..s.len() - 1])?,
+            )
+        } else if let Some(rest) = s.strip_prefix('#') {
+            (ParameterMode::Immediate, Expression::parse(rest)?)
+        } else if let Some(rest) = s.strip_prefix("rb") {
+            let rest = rest.trim();
+
+            let expression = if rest.is_empty() {
+                Expression::Number(0)
+            } else if let Some(rest) = rest.strip_prefix('+') {
+                Expression::parse(rest)?
+            } else if rest.starts_with('-') {
+                Expression::Number(rest.replace(' ', "").parse().ok()?)
+            } else {
+                return None;
+            };
+
+            (ParameterMode::Relative, expression)
+        } else {
+            return None;
+        };
+
+        Some(Self { mode, expression })
+    }
+}
+
+enum Statement {
+    Instruction(ValueType, Vec<Operand>),
+    Data(Vec<Expression>),
+}
+
+impl Statement {
+    fn len(&self) -> usize {
+        match self {
+            Statement::Instruction(_, operands) => 1 + operands.len(),
+            Statement::Data(values) => values.len(),
+        }
+    }
+
+    fn parse(s: &str, line: usize) -> Result<Self, AssembleError> {
+        let (mnemonic, rest) = match s.find(char::is_whitespace) {
+            Some(i) => (&s[..i], s[i..].trim()),
+            None => (s, ""),
+        };
+
+        let arguments = if rest.is_empty() {
+            vec![]
+        } else {
+            rest.split(',').collect::<Vec<_>>()
+        };
+
+        if mnemonic.eq_ignore_ascii_case("data") {
+            return arguments
+                .iter()
+                .map(|&argument| {
+                    Expression::parse(argument).ok_or_else(|| AssembleError::InvalidOperand {
+                        line,
+                        operand: argument.trim().to_string(),
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(Statement::Data);
+        }
+
+        let unknown = || AssembleError::UnknownMnemonic {
+            line,
+            mnemonic: mnemonic.to_string(),
+        };
+
+        // An encoding in parentheses keeps redundant mode digits, as in HLT(10099)
+        let (name, encoding) = match mnemonic
+            .strip_suffix(')')
+            .and_then(|mnemonic| mnemonic.split_once('('))
+        {
+            Some((name, encoding)) => (name, Some(encoding.parse().map_err(|_| unknown())?)),
+            None => (mnemonic, None),
+        };
+
+        let opcode = Opcode::ALL
+            .iter()
+            .cloned()
+            .find(|opcode| opcode.mnemonic().eq_ignore_ascii_case(name))
+            .ok_or_else(unknown)?;
+
+        if arguments.len() != opcode.arity() {
+            return Err(AssembleError::WrongOperandCount {
+                line,
+                expected: opcode.arity(),
+                found: arguments.len(),
+            });
+        }
+
+        let operands = arguments
+            .iter()
+            .map(|&argument| {
+                Operand::parse(argument).ok_or_else(|| AssembleError::InvalidOperand {
+                    line,
+                    operand: argument.trim().to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let modes = operands
+            .iter()
+            .map(|operand| operand.mode)
+            .collect::<Vec<_>>();
+        let canonical = Instruction::new(opcode, &modes).encode();
+
+        match encoding {
+            None => Ok(Statement::Instruction(canonical, operands)),
+            Some(encoding)
+                if Opcode::parse(encoding).is_ok_and(|instruction| {
+                    instruction.opcode() == Some(opcode) && instruction.modes() == &modes[..]
+                }) =>
+            {
+                Ok(Statement::Instruction(encoding, operands))
+            }
+            Some(_) => Err(unknown()),
+        }
+    }
+
+    fn emit(
+        &self,
+        labels: &HashMap<String, usize>,
+        line: usize,
+        values: &mut Vec<ValueType>,
+    ) -> Result<(), AssembleError> {
+        match self {
+            Statement::Instruction(encoding, operands) => {
+                values.push(*encoding);
+
+                for operand in operands {
+                    values.push(operand.expression.resolve(labels, line)?);
+                }
+            }
+            Statement::Data(expressions) => {
+                for expression in expressions {
+                    values.push(expression.resolve(labels, line)?);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && s != "rb"
+}
+
+pub fn assemble(source: &str) -> Result<Program, AssembleError> {
+    let mut labels = HashMap::new();
+    let mut statements = vec![];
+    let mut address = 0;
+
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let mut text = text.split(';').next().unwrap_or("").trim();
+
+        while let Some(colon) = text.find(':') {
+            let label = text[..colon].trim();
+
+            if let Ok(expected) = label.parse::<usize>() {
+                if expected != address {
+                    return Err(AssembleError::AddressMismatch {
+                        line,
+                        expected,
+                        found: address,
+                    });
+                }
+            } else if !is_identifier(label) {
+                return Err(AssembleError::InvalidLabel {
+                    line,
+                    label: label.to_string(),
+                });
+            } else if labels.insert(label.to_string(), address).is_some() {
+                return Err(AssembleError::DuplicateLabel {
+                    line,
+                    label: label.to_string(),
+                });
+            }
+
+            text = text[colon + 1..].trim();
+        }
+
+        if !text.is_empty() {
+            let statement = Statement::parse(text, line)?;
+
+            address += statement.len();
+            statements.push((line, statement));
+        }
+    }
+
+    let mut values = vec![];
+
+    for (line, statement) in &statements {
+        statement.emit(&labels, *line, &mut values)?;
+    }
+
+    Ok(Program::new(&values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm;
+    use crate::Computer;
+
+    #[test]
+    fn instructions() {
+        let source = "ADD [4], #3, [4]\n\
+                      mul rb+1, rb-2, rb\n\
+                      HLT";
+
+        assert_eq!(
+            Ok(Program::new(&[1001, 4, 3, 4, 22202, 1, -2, 0, 99])),
+            assemble(source)
+        );
+    }
+
+    #[test]
+    fn labels_and_data() {
+        let source = "; count down from the value in counter\n\
+                      loop:   OUT [counter]\n\
+                      \x20       ADD [counter], #-1, [counter]\n\
+                      \x20       JT [counter], #loop     ; keep going\n\
+                      \x20       HLT\n\
+                      counter: DATA 3\n\
+                      table:   data table+1, -7";
+
+        let program = assemble(source).unwrap();
+
+        assert_eq!(
+            Program::new(&[4, 10, 1001, 10, -1, 10, 1005, 10, 0, 99, 3, 12, -7]),
+            program
+        );
+
+        let mut computer = Computer::new(&program.to_string()).unwrap();
+        let (_, receiver) = computer.get_io();
+
+        computer.run().unwrap();
+
+        assert_eq!(vec![3, 2, 1], receiver.try_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Err(AssembleError::UnknownMnemonic {
+                line: 2,
+                mnemonic: "NOP".to_string()
+            }),
+            assemble("HLT\nNOP")
+        );
+        assert_eq!(
+            Err(AssembleError::WrongOperandCount {
+                line: 1,
+                expected: 1,
+                found: 2
+            }),
+            assemble("OUT #1, #2")
+        );
+        assert_eq!(
+            Err(AssembleError::InvalidOperand {
+                line: 1,
+                operand: "4".to_string()
+            }),
+            assemble("OUT 4")
+        );
+        assert_eq!(
+            Err(AssembleError::UnknownMnemonic {
+                line: 1,
+                mnemonic: "OUT(4)".to_string()
+            }),
+            assemble("OUT(4) #1")
+        );
+        assert_eq!(
+            Err(AssembleError::UndefinedLabel {
+                line: 1,
+                label: "end".to_string()
+            }),
+            assemble("JT #1, #end")
+        );
+        assert_eq!(
+            Err(AssembleError::DuplicateLabel {
+                line: 2,
+                label: "a".to_string()
+            }),
+            assemble("a: HLT\na: HLT")
+        );
+        assert_eq!(
+            Err(AssembleError::AddressMismatch {
+                line: 2,
+                expected: 3,
+                found: 1
+            }),
+            assemble("HLT\n3: HLT")
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let programs = [
+            "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,\
+             1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,\
+             999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+            "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+            "10004,3,20099,7,-1",
+        ];
+
+        for &program in &programs {
+            let program = program.parse::<Program>().unwrap();
+
+            assert_eq!(Ok(program.clone()), assemble(&disasm::listing(&program)));
+        }
+    }
+}
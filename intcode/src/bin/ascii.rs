@@ -0,0 +1,17 @@
+use std::env;
+use std::error::Error;
+
+use intcode::ascii::AsciiIo;
+use intcode::Computer;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "input.txt".to_string());
+    let mut computer = Computer::from_path(path)?;
+
+    computer.attach_io(Box::new(AsciiIo::terminal()));
+    computer.run()?;
+
+    Ok(())
+}
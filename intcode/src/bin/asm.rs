@@ -0,0 +1,16 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+
+use intcode::asm;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "input.asm".to_string());
+    let program = asm::assemble(&fs::read_to_string(path)?)?;
+
+    println!("{}", program);
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use std::env;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use intcode::profile::Profiler;
+use intcode::{asm, Computer, ValueType};
+
+const SOURCE: &str = "\
+        JT #1, #start
+3: n:   DATA 0
+i:      DATA 0
+t:      DATA 0
+c:      DATA 0
+acc:    DATA 0
+start:  ADD [i], #1, [i]
+        MUL [i], [i], [t]
+        ADD [acc], [t], [acc]
+        ARB #1
+        ADD rb+0, [i], rb+100
+        LT [i], [n], [c]
+        JT [c], #start
+        OUT [acc]
+        HLT";
+
+const ITERATIONS: usize = 3;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let n = env::args()
+        .nth(1)
+        .map(|n| n.parse::<ValueType>())
+        .transpose()?
+        .unwrap_or(1_000_000);
+    let program = asm::assemble(SOURCE)?.to_string();
+
+    let profiler = Arc::new(Mutex::new(Profiler::new()));
+    let mut computer = Computer::new(&program)?;
+
+    computer.attach_tracer(Box::new(profiler.clone()));
+    computer.run_with_values(3, &[n])?;
+
+    let instructions = profiler.lock().unwrap().instructions();
+
+    println!("{} instructions", instructions);
+
+    // Decoding every instruction again is how the computer ran before the
+    // decode cache, so it serves as the baseline
+    for &(name, cache) in &[("uncached", false), ("cached", true)] {
+        let mut computer = Computer::new(&program)?.decode_cache(cache);
+        let start = Instant::now();
+
+        for _ in 0..ITERATIONS {
+            computer.run_with_values(3, &[n])?;
+        }
+
+        let seconds = start.elapsed().as_secs_f64() / ITERATIONS as f64;
+
+        println!(
+            "{:>8}: {:.3}s, {:.0} instructions per second",
+            name,
+            seconds,
+            instructions as f64 / seconds
+        );
+    }
+
+    Ok(())
+}
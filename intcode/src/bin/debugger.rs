@@ -0,0 +1,300 @@
+use std::env;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+
+use intcode::debugger::{Debugger, Stop};
+use intcode::{disasm, Computer, ExecutionError, ValueType};
+
+const HELP: &str = "\
+break <address>      b   set a breakpoint
+delete <address>     d   remove a breakpoint
+watch <address>      w   break when the address is written
+unwatch <address>        remove a watchpoint
+step [count]         s   execute instructions one at a time
+continue             c   run until a breakpoint, watchpoint, input or halt
+back [count]         bs  undo instructions one at a time
+rwrite <address>         run back to the last write of an address
+routput                  run back to the last output
+history <length>         set how many instructions can be undone
+input <values...>    i   queue input values
+registers            r   show ip and relative base
+memory <addr> [len]  x   dump memory
+list [addr] [count]  l   disassemble from the ip or an address
+reset                    reload the program
+quit                 q   exit";
+
+fn parse_arguments<T: std::str::FromStr>(arguments: &[&str]) -> Option<Vec<T>> {
+    arguments
+        .iter()
+        .map(|argument| argument.parse().ok())
+        .collect()
+}
+
+fn report(debugger: &mut Debugger, result: Result<Stop, ExecutionError>, output: &mut String) {
+    for value in debugger.take_output() {
+        writeln!(output, "output: {}", value).unwrap();
+    }
+
+    match result {
+        Ok(Stop::Step) => (),
+        Ok(Stop::Breakpoint(address)) => writeln!(output, "breakpoint at {}", address).unwrap(),
+        Ok(Stop::Watchpoint(write)) => writeln!(
+            output,
+            "watchpoint at {}: {} -> {}",
+            write.address, write.old, write.new
+        )
+        .unwrap(),
+        Ok(Stop::NeedsInput) => writeln!(output, "waiting for input").unwrap(),
+        Ok(Stop::Halted) => writeln!(output, "halted").unwrap(),
+        Err(error) => writeln!(output, "error: {}", error).unwrap(),
+    }
+
+    list(debugger, debugger.registers().ip, 1, output);
+}
+
+fn list(debugger: &mut Debugger, mut address: usize, count: usize, output: &mut String) {
+    let memory = debugger.computer().memory().to_vec();
+
+    for _ in 0..count {
+        match disasm::instruction(&memory, address) {
+            Some(line) => {
+                writeln!(output, "{}", line).unwrap();
+                address += line.values.len();
+            }
+            None => {
+                writeln!(
+                    output,
+                    "{:>5}: DATA {}",
+                    address,
+                    memory.get(address).unwrap_or(&0)
+                )
+                .unwrap();
+                address += 1;
+            }
+        }
+    }
+}
+
+fn execute(
+    debugger: &mut Debugger,
+    command: &str,
+    arguments: &[&str],
+    output: &mut String,
+) -> Option<()> {
+    match command {
+        "break" | "b" => {
+            for address in parse_arguments(arguments)? {
+                debugger.add_breakpoint(address);
+            }
+        }
+        "delete" | "d" => {
+            for address in parse_arguments(arguments)? {
+                debugger.remove_breakpoint(address);
+            }
+        }
+        "watch" | "w" => {
+            for address in parse_arguments(arguments)? {
+                debugger.add_watchpoint(address);
+            }
+        }
+        "unwatch" => {
+            for address in parse_arguments(arguments)? {
+                debugger.remove_watchpoint(address);
+            }
+        }
+        "step" | "s" => {
+            let count = parse_arguments(arguments)?.first().cloned().unwrap_or(1);
+
+            // Stop at the first step that does not simply run an instruction
+            let mut result = Ok(Stop::Step);
+
+            for _ in 0..count {
+                result = debugger.step();
+
+                if result != Ok(Stop::Step) {
+                    break;
+                }
+            }
+
+            if count > 0 {
+                report(debugger, result, output);
+            }
+        }
+        "continue" | "c" => {
+            let result = debugger.resume();
+            report(debugger, result, output);
+        }
+        "back" | "bs" => {
+            let count = parse_arguments(arguments)?.first().cloned().unwrap_or(1);
+
+            if (0..count).any(|_| !debugger.step_back()) {
+                writeln!(output, "reached the start of the history").unwrap();
+            }
+
+            list(debugger, debugger.registers().ip, 1, output);
+        }
+        "rwrite" => {
+            let address = *parse_arguments(arguments)?.first()?;
+
+            match debugger.reverse_to_write(address) {
+                Some(write) => writeln!(
+                    output,
+                    "write at {}: {} -> {}",
+                    write.address, write.old, write.new
+                )
+                .unwrap(),
+                None => writeln!(output, "no write to {} in the history", address).unwrap(),
+            }
+
+            list(debugger, debugger.registers().ip, 1, output);
+        }
+        "routput" => {
+            match debugger.reverse_to_output() {
+                Some(value) => writeln!(output, "output: {}", value).unwrap(),
+                None => writeln!(output, "no output in the history").unwrap(),
+            }
+
+            list(debugger, debugger.registers().ip, 1, output);
+        }
+        "history" => {
+            let length = *parse_arguments(arguments)?.first()?;
+
+            debugger.set_history_limit(length);
+        }
+        "input" | "i" => {
+            for value in parse_arguments::<ValueType>(arguments)? {
+                debugger.provide_input(value);
+            }
+        }
+        "registers" | "r" => {
+            let registers = debugger.registers();
+
+            writeln!(
+                output,
+                "ip: {} relative base: {}",
+                registers.ip, registers.relative_base
+            )
+            .unwrap();
+        }
+        "memory" | "x" => {
+            let arguments = parse_arguments(arguments)?;
+            let address = *arguments.first()?;
+            let length = arguments.get(1).cloned().unwrap_or(8);
+
+            for (i, chunk) in debugger.memory(address, length).chunks(8).enumerate() {
+                let values = chunk
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>();
+
+                writeln!(output, "{:>5}: {}", address + i * 8, values.join(", ")).unwrap();
+            }
+        }
+        "list" | "l" => {
+            let arguments = parse_arguments(arguments)?;
+            let address = arguments
+                .first()
+                .cloned()
+                .unwrap_or_else(|| debugger.registers().ip);
+
+            list(
+                debugger,
+                address,
+                arguments.get(1).cloned().unwrap_or(10),
+                output,
+            );
+        }
+        "reset" => {
+            if let Err(error) = debugger.computer().reset() {
+                writeln!(output, "error: {}", error).unwrap();
+            }
+
+            debugger.clear_history();
+        }
+        "help" | "h" => writeln!(output, "{}", HELP).unwrap(),
+        _ => return None,
+    }
+
+    Some(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "input.txt".to_string());
+    let mut debugger = Debugger::new(Computer::from_path(path)?);
+
+    let mut output = String::new();
+
+    list(&mut debugger, 0, 1, &mut output);
+    print!("{}", output);
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        print!("(icd) ");
+        io::stdout().flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let mut output = String::new();
+
+        match words.split_first() {
+            Some((&"quit", _)) | Some((&"q", _)) => break,
+            Some((command, arguments))
+                if execute(&mut debugger, command, arguments, &mut output).is_none() =>
+            {
+                println!("invalid command, type help for a list of commands")
+            }
+            _ => (),
+        }
+
+        print!("{}", output);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(program: &str, commands: &[&str]) -> (Debugger, String) {
+        let mut debugger = Debugger::new(Computer::new(program).unwrap());
+        let mut output = String::new();
+
+        for command in commands {
+            let words = command.split_whitespace().collect::<Vec<_>>();
+
+            execute(&mut debugger, words[0], &words[1..], &mut output).unwrap();
+        }
+
+        (debugger, output)
+    }
+
+    #[test]
+    fn step_stops_at_watchpoints() {
+        let (debugger, output) = session("1101,1,2,9,1101,3,4,10,99,0,0", &["watch 9", "step 3"]);
+
+        assert_eq!(
+            "watchpoint at 9: 0 -> 3\n    \
+             4: ADD #3, #4, [10]                ; 1101,3,4,10\n",
+            output
+        );
+        assert_eq!(4, debugger.registers().ip);
+    }
+
+    #[test]
+    fn step_zero_does_nothing() {
+        let (debugger, output) = session("104,1,99", &["step 0"]);
+
+        assert_eq!("", output);
+        assert_eq!(0, debugger.registers().ip);
+    }
+}
@@ -0,0 +1,15 @@
+use std::env;
+use std::error::Error;
+
+use intcode::{disasm, Program};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "input.txt".to_string());
+    let program = Program::from_file(path)?;
+
+    print!("{}", disasm::listing(&program));
+
+    Ok(())
+}
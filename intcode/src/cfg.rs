@@ -0,0 +1,285 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use super::disasm::{self, Decoded, Line};
+use super::{Opcode, ParameterMode, Program};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Region {
+    Code,
+    Data,
+    SelfModified,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    pub end: usize,
+    pub instructions: Vec<Line>,
+    pub successors: Vec<usize>,
+    pub indirect: bool,
+    pub self_modified: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Graph {
+    pub blocks: Vec<Block>,
+    pub regions: Vec<Region>,
+}
+
+fn is_terminator(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        Opcode::Halt | Opcode::JumpIfTrue | Opcode::JumpIfFalse
+    )
+}
+
+// A jump is computed when its target is read from memory and the condition
+// does not rule out ever taking it
+fn is_indirect(instruction: &Decoded) -> bool {
+    match (instruction.opcode, &instruction.parameters[..]) {
+        (Opcode::JumpIfTrue, [condition, target]) | (Opcode::JumpIfFalse, [condition, target]) => {
+            let never_taken = condition.mode == ParameterMode::Immediate
+                && (condition.value != 0) != (instruction.opcode == Opcode::JumpIfTrue);
+
+            target.mode != ParameterMode::Immediate && !never_taken
+        }
+        _ => false,
+    }
+}
+
+// Only position mode writes have a target known before running the program
+fn write_target(instruction: &Decoded) -> Option<usize> {
+    let writes = instruction.opcode.writes();
+
+    instruction
+        .parameters
+        .iter()
+        .enumerate()
+        .find(|&(i, _)| writes & 1 << i != 0)
+        .map(|(_, parameter)| parameter)
+        .filter(|parameter| parameter.mode == ParameterMode::Position && parameter.value >= 0)
+        .map(|parameter| parameter.value as usize)
+}
+
+// Blocks start at address 0, at jump targets, after jumps and halts, and at
+// return addresses pushed onto the stack, which are only reached indirectly
+pub fn analyze(program: &Program) -> Graph {
+    let values = program.values();
+    let reached = disasm::reachable(values);
+    let decoded = (0..values.len())
+        .filter(|&address| reached[address])
+        .filter_map(|address| Decoded::at(values, address).map(|decoded| (address, decoded)))
+        .collect::<Vec<_>>();
+
+    let mut leaders = BTreeSet::new();
+    let mut regions = vec![Region::Data; values.len()];
+
+    leaders.insert(0);
+
+    for (address, instruction) in &decoded {
+        let next = address + instruction.len();
+        let successors = instruction.successors(*address);
+
+        for region in &mut regions[*address..next] {
+            *region = Region::Code;
+        }
+
+        if is_terminator(instruction.opcode) {
+            leaders.extend(successors);
+            leaders.insert(next);
+        } else {
+            leaders.extend(
+                successors
+                    .into_iter()
+                    .filter(|&successor| successor != next),
+            );
+        }
+    }
+
+    for (_, instruction) in &decoded {
+        if let Some(target) = write_target(instruction) {
+            if regions.get(target) == Some(&Region::Code) {
+                regions[target] = Region::SelfModified;
+            }
+        }
+    }
+
+    let mut blocks: Vec<Block> = vec![];
+    let mut open = false;
+
+    for (address, instruction) in decoded {
+        let continues = open
+            && blocks.last().map(|block| block.end) == Some(address)
+            && !leaders.contains(&address);
+
+        if !continues {
+            blocks.push(Block {
+                start: address,
+                end: address,
+                instructions: vec![],
+                successors: vec![],
+                indirect: false,
+                self_modified: false,
+            });
+        }
+
+        let block = blocks.last_mut().unwrap();
+
+        block.end = address + instruction.len();
+        block
+            .instructions
+            .extend(disasm::instruction(values, address));
+        block.self_modified |= regions[address..block.end].contains(&Region::SelfModified);
+        block.indirect = is_indirect(&instruction);
+
+        open = !is_terminator(instruction.opcode);
+
+        block.successors = if open {
+            vec![block.end]
+        } else {
+            instruction.successors(address)
+        };
+
+        block
+            .successors
+            .retain(|&successor| reached.get(successor) == Some(&true));
+        block.successors.dedup();
+    }
+
+    Graph { blocks, regions }
+}
+
+impl Graph {
+    pub fn block(&self, address: usize) -> Option<&Block> {
+        self.blocks
+            .iter()
+            .find(|block| (block.start..block.end).contains(&address))
+    }
+
+    pub fn dot(&self) -> String {
+        let mut dot = String::new();
+
+        writeln!(dot, "digraph cfg {{").unwrap();
+        writeln!(dot, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+
+        if self.blocks.iter().any(|block| block.indirect) {
+            writeln!(dot, "    indirect [label=\"?\", shape=circle];").unwrap();
+        }
+
+        for block in &self.blocks {
+            let label = block
+                .instructions
+                .iter()
+                .map(|line| format!("{}: {}\\l", line.address, line.text))
+                .collect::<String>();
+            let style = if block.self_modified {
+                ", style=filled, fillcolor=lightgrey"
+            } else {
+                ""
+            };
+
+            writeln!(dot, "    n{} [label=\"{}\"{}];", block.start, label, style).unwrap();
+        }
+
+        for block in &self.blocks {
+            for successor in &block.successors {
+                writeln!(dot, "    n{} -> n{};", block.start, successor).unwrap();
+            }
+
+            if block.indirect {
+                writeln!(dot, "    n{} -> indirect [style=dashed];", block.start).unwrap();
+            }
+        }
+
+        writeln!(dot, "}}").unwrap();
+
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(graph: &Graph) -> Vec<(usize, usize, Vec<usize>)> {
+        graph
+            .blocks
+            .iter()
+            .map(|block| (block.start, block.end, block.successors.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn branches() {
+        let program = "3,14,1005,14,10,104,0,1105,1,12,104,1,99,0,0"
+            .parse::<Program>()
+            .unwrap();
+        let graph = analyze(&program);
+
+        assert_eq!(
+            vec![
+                (0, 5, vec![5, 10]),
+                (5, 10, vec![12]),
+                (10, 12, vec![12]),
+                (12, 13, vec![]),
+            ],
+            spans(&graph)
+        );
+        assert_eq!(&[Region::Data, Region::Data], &graph.regions[13..]);
+        assert!(graph.blocks.iter().all(|block| !block.indirect));
+        assert_eq!(Some(5), graph.block(8).map(|block| block.start));
+    }
+
+    #[test]
+    fn computed_jumps() {
+        let program = "109,20,21101,9,0,0,1105,1,10,99,2106,0,0"
+            .parse::<Program>()
+            .unwrap();
+        let graph = analyze(&program);
+
+        assert_eq!(
+            vec![(0, 9, vec![10]), (9, 10, vec![]), (10, 13, vec![])],
+            spans(&graph)
+        );
+        assert_eq!(
+            vec![false, false, true],
+            graph
+                .blocks
+                .iter()
+                .map(|block| block.indirect)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn self_modifying_code() {
+        let program = "1101,5,6,5,104,0,99".parse::<Program>().unwrap();
+        let graph = analyze(&program);
+
+        assert_eq!(vec![(0, 7, vec![])], spans(&graph));
+        assert!(graph.blocks[0].self_modified);
+        assert_eq!(Region::SelfModified, graph.regions[5]);
+        assert_eq!(Region::Code, graph.regions[4]);
+    }
+
+    #[test]
+    fn dot() {
+        let program = "109,20,21101,9,0,0,1105,1,10,99,2106,0,0"
+            .parse::<Program>()
+            .unwrap();
+
+        assert_eq!(
+            "digraph cfg {\n    \
+             node [shape=box, fontname=\"monospace\"];\n    \
+             indirect [label=\"?\", shape=circle];\n    \
+             n0 [label=\"0: ARB #20\\l2: ADD #9, #0, rb+0\\l6: JT #1, #10\\l\"];\n    \
+             n9 [label=\"9: HLT\\l\"];\n    \
+             n10 [label=\"10: JF #0, rb+0\\l\"];\n    \
+             n0 -> n10;\n    \
+             n10 -> indirect [style=dashed];\n\
+             }\n",
+            analyze(&program).dot()
+        );
+    }
+}
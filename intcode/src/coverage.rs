@@ -0,0 +1,336 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::disasm::{self, Decoded};
+use super::trace::{Trace, Tracer};
+use super::{Opcode, Program};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Branch {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+impl Branch {
+    pub fn covered(&self) -> bool {
+        self.taken > 0 && self.not_taken > 0
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Coverage {
+    hits: Vec<u64>,
+    branches: BTreeMap<usize, Branch>,
+    runs: u64,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A run starts whenever the computer resets, so resuming a computer after
+    // it stopped for input or an error counts towards the same run
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    pub fn hits(&self, address: usize) -> u64 {
+        self.hits.get(address).cloned().unwrap_or(0)
+    }
+
+    pub fn branch(&self, address: usize) -> Option<Branch> {
+        self.branches.get(&address).cloned()
+    }
+
+    pub fn merge(&mut self, other: &Coverage) {
+        if other.hits.len() > self.hits.len() {
+            self.hits.resize(other.hits.len(), 0);
+        }
+
+        for (hits, other) in self.hits.iter_mut().zip(&other.hits) {
+            *hits += other;
+        }
+
+        for (&address, other) in &other.branches {
+            let branch = self.branches.entry(address).or_default();
+
+            branch.taken += other.taken;
+            branch.not_taken += other.not_taken;
+        }
+
+        self.runs += other.runs;
+    }
+
+    pub fn uncovered(&self, program: &Program) -> Vec<usize> {
+        instructions(self, program)
+            .into_iter()
+            .filter(|&address| self.hits(address) == 0)
+            .collect()
+    }
+
+    pub fn annotate(&self, program: &Program) -> String {
+        let mut listing = String::new();
+
+        for line in disasm::disassemble(program) {
+            let count = match self.hits(line.address) {
+                _ if !line.code => "-".to_string(),
+                0 => "#####".to_string(),
+                hits => hits.to_string(),
+            };
+
+            write!(listing, "{:>8} | {}", count, line).unwrap();
+
+            if let Some(branch) = self.branch(line.address) {
+                write!(
+                    listing,
+                    " (taken {}, not taken {})",
+                    branch.taken, branch.not_taken
+                )
+                .unwrap();
+            }
+
+            writeln!(listing).unwrap();
+        }
+
+        listing
+    }
+
+    // LCOV line numbers start at 1, so address n is reported as line n + 1.
+    // Each conditional jump is a block with a taken and a not taken branch.
+    pub fn lcov(&self, program: &Program, name: &str) -> String {
+        let values = program.values();
+        let addresses = instructions(self, program);
+        let conditional = addresses
+            .iter()
+            .cloned()
+            .filter(|&address| {
+                Decoded::at(values, address).is_some_and(|decoded| {
+                    matches!(decoded.opcode, Opcode::JumpIfTrue | Opcode::JumpIfFalse)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut lcov = String::new();
+
+        writeln!(lcov, "TN:").unwrap();
+        writeln!(lcov, "SF:{}", name).unwrap();
+
+        for &address in &conditional {
+            let branch = self.branch(address);
+
+            for (i, count) in [branch.map(|b| b.taken), branch.map(|b| b.not_taken)]
+                .iter()
+                .enumerate()
+            {
+                let count = count.map_or("-".to_string(), |count| count.to_string());
+
+                writeln!(lcov, "BRDA:{},0,{},{}", address + 1, i, count).unwrap();
+            }
+        }
+
+        // Branches only reached through computed jumps or self-modified code
+        // have no BRDA line, so they are left out of BRH as well
+        let branches_hit = conditional
+            .iter()
+            .filter_map(|&address| self.branch(address))
+            .map(|branch| (branch.taken > 0) as usize + (branch.not_taken > 0) as usize)
+            .sum::<usize>();
+
+        writeln!(lcov, "BRF:{}", conditional.len() * 2).unwrap();
+        writeln!(lcov, "BRH:{}", branches_hit).unwrap();
+
+        for &address in &addresses {
+            writeln!(lcov, "DA:{},{}", address + 1, self.hits(address)).unwrap();
+        }
+
+        writeln!(lcov, "LF:{}", addresses.len()).unwrap();
+        writeln!(
+            lcov,
+            "LH:{}",
+            addresses
+                .iter()
+                .filter(|&&address| self.hits(address) > 0)
+                .count()
+        )
+        .unwrap();
+        writeln!(lcov, "end_of_record").unwrap();
+
+        lcov
+    }
+}
+
+// Statically reachable instructions, plus any that only computed jumps led to
+fn instructions(coverage: &Coverage, program: &Program) -> Vec<usize> {
+    disasm::disassemble(program)
+        .iter()
+        .filter(|line| line.code)
+        .map(|line| line.address)
+        .chain((0..coverage.hits.len()).filter(|&address| coverage.hits[address] > 0))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+impl Tracer for Coverage {
+    fn trace(&mut self, trace: &Trace) {
+        if trace.ip >= self.hits.len() {
+            self.hits.resize(trace.ip + 1, 0);
+        }
+
+        self.hits[trace.ip] += 1;
+
+        let taken = match trace.builtin {
+            Some(Opcode::JumpIfTrue) => trace.operands.first().map(|&condition| condition != 0),
+            Some(Opcode::JumpIfFalse) => trace.operands.first().map(|&condition| condition == 0),
+            _ => None,
+        };
+
+        if let Some(taken) = taken {
+            let branch = self.branches.entry(trace.ip).or_default();
+
+            if taken {
+                branch.taken += 1;
+            } else {
+                branch.not_taken += 1;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.runs += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Computer, ValueType};
+    use std::sync::{Arc, Mutex};
+
+    const JUMP_TEST: &str = "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9";
+
+    fn covered(inputs: &[ValueType]) -> Coverage {
+        let coverage = Arc::new(Mutex::new(Coverage::new()));
+        let mut computer = Computer::new(JUMP_TEST).unwrap();
+
+        computer.attach_tracer(Box::new(coverage.clone()));
+
+        for &input in inputs {
+            computer.provide_input(input);
+            computer.run().unwrap();
+        }
+
+        let coverage = coverage.lock().unwrap().clone();
+        coverage
+    }
+
+    #[test]
+    fn hits_and_branches() {
+        let program = JUMP_TEST.parse::<Program>().unwrap();
+        let coverage = covered(&[0]);
+
+        assert_eq!(1, coverage.runs());
+        assert_eq!(1, coverage.hits(2));
+        assert_eq!(0, coverage.hits(5));
+        assert_eq!(
+            Some(Branch {
+                taken: 1,
+                not_taken: 0
+            }),
+            coverage.branch(2)
+        );
+        assert_eq!(vec![5], coverage.uncovered(&program));
+
+        let coverage = covered(&[0, 3, 0]);
+
+        assert_eq!(3, coverage.runs());
+        assert!(coverage.branch(2).unwrap().covered());
+        assert!(coverage.uncovered(&program).is_empty());
+    }
+
+    #[test]
+    fn custom_opcodes_are_not_branches() {
+        use crate::isa::InstructionSet;
+        use crate::State;
+
+        let set = InstructionSet::new()
+            .define(5, 0, &[], |_| Ok(State::Running))
+            .define(6, 1, &[], |_| Ok(State::Running));
+        let coverage = Arc::new(Mutex::new(Coverage::new()));
+        let mut computer = Computer::new("5,6,0,99").unwrap().instruction_set(set);
+
+        computer.attach_tracer(Box::new(coverage.clone()));
+        computer.run().unwrap();
+
+        let coverage = coverage.lock().unwrap();
+
+        assert_eq!(vec![1, 1, 0, 1], coverage.hits);
+        assert!(coverage.branches.is_empty());
+    }
+
+    #[test]
+    fn merge() {
+        let mut coverage = covered(&[0]);
+
+        coverage.merge(&covered(&[7]));
+
+        assert_eq!(covered(&[0, 7]), coverage);
+    }
+
+    #[test]
+    fn annotate() {
+        let program = JUMP_TEST.parse::<Program>().unwrap();
+        let listing = covered(&[0]).annotate(&program);
+        let lines = listing.lines().collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                "       1 |     0: IN [12]                         ; 3,12",
+                "       1 |     2: JF [12], [15]                   ; 6,12,15 \
+                 (taken 1, not taken 0)",
+                "   ##### |     5: ADD [13], [14], [13]            ; 1,13,14,13",
+                "       1 |     9: OUT [13]                        ; 4,13",
+                "       1 |    11: HLT                             ; 99",
+                "       - |    12: DATA -1, 0, 1, 9",
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn lcov() {
+        let program = JUMP_TEST.parse::<Program>().unwrap();
+
+        assert_eq!(
+            "TN:\nSF:jump.int\n\
+             BRDA:3,0,0,1\nBRDA:3,0,1,0\nBRF:2\nBRH:1\n\
+             DA:1,1\nDA:3,1\nDA:6,0\nDA:10,1\nDA:12,1\nLF:5\nLH:4\n\
+             end_of_record\n",
+            covered(&[0]).lcov(&program, "jump.int")
+        );
+    }
+
+    #[test]
+    fn lcov_skips_dynamic_branches() {
+        let program = "1101,1005,0,4,0,9,8,99,99,1".parse::<Program>().unwrap();
+        let coverage = Arc::new(Mutex::new(Coverage::new()));
+        let mut computer = Computer::from_program(&program);
+
+        computer.attach_tracer(Box::new(coverage.clone()));
+        computer.run().unwrap();
+
+        let coverage = coverage.lock().unwrap();
+
+        assert_eq!(
+            Some(Branch {
+                taken: 1,
+                not_taken: 0
+            }),
+            coverage.branch(4)
+        );
+        assert!(coverage
+            .lcov(&program, "modified.int")
+            .contains("BRF:0\nBRH:0\n"));
+    }
+}
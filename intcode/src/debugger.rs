@@ -0,0 +1,436 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use super::{Computer, ExecutionError, MemoryWrite, Registers, State, ValueType};
+
+const HISTORY_LIMIT: usize = 1 << 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stop {
+    Step,
+    Breakpoint(usize),
+    Watchpoint(MemoryWrite),
+    NeedsInput,
+    Halted,
+}
+
+// Everything needed to undo one executed instruction
+struct Undo {
+    registers: Registers,
+    halted: bool,
+    write: Option<MemoryWrite>,
+    input: Option<ValueType>,
+    output: Option<ValueType>,
+}
+
+pub struct Debugger {
+    computer: Computer,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<usize>,
+    output: Vec<ValueType>,
+    history: VecDeque<Undo>,
+    history_limit: usize,
+    stopped_at: Option<usize>,
+}
+
+impl Debugger {
+    pub fn new(computer: Computer) -> Self {
+        Self {
+            computer,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            output: vec![],
+            history: VecDeque::new(),
+            history_limit: HISTORY_LIMIT,
+            stopped_at: None,
+        }
+    }
+
+    pub fn history_limit(mut self, limit: usize) -> Self {
+        self.set_history_limit(limit);
+
+        self
+    }
+
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+
+        while self.history.len() > limit {
+            self.history.pop_front();
+        }
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    // The history only stays valid while the computer is driven through the
+    // debugger, so it has to be cleared after resetting or patching memory
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    pub fn computer(&mut self) -> &mut Computer {
+        &mut self.computer
+    }
+
+    pub fn add_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &usize> {
+        self.breakpoints.iter()
+    }
+
+    pub fn add_watchpoint(&mut self, address: usize) {
+        self.watchpoints.insert(address);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: usize) -> bool {
+        self.watchpoints.remove(&address)
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = &usize> {
+        self.watchpoints.iter()
+    }
+
+    pub fn provide_input(&mut self, value: ValueType) {
+        self.computer.provide_input(value);
+    }
+
+    pub fn take_output(&mut self) -> Vec<ValueType> {
+        self.output.split_off(0)
+    }
+
+    pub fn registers(&self) -> Registers {
+        self.computer.registers()
+    }
+
+    pub fn memory(&self, address: usize, length: usize) -> Vec<ValueType> {
+        let memory = self.computer.memory();
+
+        (address..address + length)
+            .map(|address| memory.get(address).cloned().unwrap_or(0))
+            .collect()
+    }
+
+    pub fn step(&mut self) -> Result<Stop, ExecutionError> {
+        let registers = self.computer.registers();
+        let halted = self.computer.halted;
+        let input = self.computer.inputs.front().cloned();
+        let inputs = self.computer.inputs.len();
+
+        let state = self.computer.step()?;
+
+        if state != State::NeedsInput {
+            self.stopped_at = None;
+        }
+
+        // Stepping a halted computer changes nothing, so there is nothing to undo
+        if state != State::NeedsInput && !halted {
+            self.record(Undo {
+                registers,
+                halted,
+                write: self.computer.last_write(),
+                input: input.filter(|_| self.computer.inputs.len() < inputs),
+                output: match state {
+                    State::Output(value) => Some(value),
+                    _ => None,
+                },
+            });
+        }
+
+        match state {
+            State::Halted => return Ok(Stop::Halted),
+            State::NeedsInput => return Ok(Stop::NeedsInput),
+            State::Output(value) => self.output.push(value),
+            State::Running => (),
+        }
+
+        match self.computer.last_write() {
+            Some(write) if self.watchpoints.contains(&write.address) => Ok(Stop::Watchpoint(write)),
+            _ => Ok(Stop::Step),
+        }
+    }
+
+    // Resuming from a breakpoint runs its instruction instead of stopping at
+    // it again, but any other breakpoint is hit before its instruction runs
+    pub fn resume(&mut self) -> Result<Stop, ExecutionError> {
+        loop {
+            let ip = self.computer.registers().ip;
+
+            if self.breakpoints.contains(&ip) && self.stopped_at != Some(ip) {
+                self.stopped_at = Some(ip);
+
+                return Ok(Stop::Breakpoint(ip));
+            }
+
+            match self.step()? {
+                Stop::Step => (),
+                stop => return Ok(stop),
+            }
+        }
+    }
+
+    fn record(&mut self, undo: Undo) {
+        if self.history_limit == 0 {
+            return;
+        }
+
+        if self.history.len() == self.history_limit {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(undo);
+    }
+
+    // Outputs not yet taken are withdrawn again; outputs that were already
+    // taken stay taken and are produced a second time when stepping forward
+    pub fn step_back(&mut self) -> bool {
+        let undo = match self.history.pop_back() {
+            Some(undo) => undo,
+            None => return false,
+        };
+
+        let computer = &mut self.computer;
+
+        if let Some(write) = undo.write {
+            computer.memory.values[write.address] = write.old;
+            computer.memory.invalidate(write.address);
+        }
+
+        computer.memory.ip = undo.registers.ip;
+        computer.memory.relative_base = undo.registers.relative_base;
+        computer.memory.last_write = None;
+        computer.halted = undo.halted;
+        self.stopped_at = None;
+
+        if let Some(value) = undo.input {
+            computer.inputs.push_front(value);
+        }
+
+        if undo.output.is_some() {
+            self.output.pop();
+        }
+
+        true
+    }
+
+    // Rewinds to just before the most recent write of the address, leaving
+    // the computer untouched if the history does not contain one
+    pub fn reverse_to_write(&mut self, address: usize) -> Option<MemoryWrite> {
+        let steps = self
+            .history
+            .iter()
+            .rev()
+            .position(|undo| undo.write.is_some_and(|write| write.address == address))?;
+        let write = self.history[self.history.len() - 1 - steps].write;
+
+        for _ in 0..=steps {
+            self.step_back();
+        }
+
+        write
+    }
+
+    pub fn reverse_to_output(&mut self) -> Option<ValueType> {
+        let steps = self
+            .history
+            .iter()
+            .rev()
+            .position(|undo| undo.output.is_some())?;
+        let output = self.history[self.history.len() - 1 - steps].output;
+
+        for _ in 0..=steps {
+            self.step_back();
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breakpoints() {
+        let computer = Computer::new("1101,1,2,9,4,9,104,7,99,0").unwrap();
+        let mut debugger = Debugger::new(computer);
+
+        debugger.add_breakpoint(4);
+        debugger.add_breakpoint(8);
+
+        assert_eq!(Ok(Stop::Breakpoint(4)), debugger.resume());
+        assert_eq!(vec![3], debugger.memory(9, 1));
+        assert_eq!(Vec::<ValueType>::new(), debugger.take_output());
+
+        assert_eq!(Ok(Stop::Breakpoint(8)), debugger.resume());
+        assert_eq!(vec![3, 7], debugger.take_output());
+
+        assert_eq!(Ok(Stop::Halted), debugger.resume());
+    }
+
+    #[test]
+    fn breakpoint_at_the_current_instruction() {
+        let computer = Computer::new("104,1,104,2,99").unwrap();
+        let mut debugger = Debugger::new(computer);
+
+        debugger.add_breakpoint(0);
+
+        assert_eq!(Ok(Stop::Breakpoint(0)), debugger.resume());
+        assert_eq!(Vec::<ValueType>::new(), debugger.take_output());
+
+        assert_eq!(Ok(Stop::Step), debugger.step());
+
+        debugger.add_breakpoint(2);
+
+        assert_eq!(Ok(Stop::Breakpoint(2)), debugger.resume());
+        assert_eq!(Ok(Stop::Halted), debugger.resume());
+        assert_eq!(vec![1, 2], debugger.take_output());
+    }
+
+    #[test]
+    fn watchpoints() {
+        let computer = Computer::new("1101,1,2,11,1101,3,4,12,3,11,99,0,0").unwrap();
+        let mut debugger = Debugger::new(computer);
+
+        debugger.add_watchpoint(11);
+
+        assert_eq!(
+            Ok(Stop::Watchpoint(MemoryWrite {
+                address: 11,
+                old: 0,
+                new: 3,
+            })),
+            debugger.resume()
+        );
+        assert_eq!(Ok(Stop::NeedsInput), debugger.resume());
+        assert_eq!(8, debugger.registers().ip);
+        assert_eq!(vec![3, 7], debugger.memory(11, 2));
+
+        debugger.provide_input(5);
+
+        assert_eq!(
+            Ok(Stop::Watchpoint(MemoryWrite {
+                address: 11,
+                old: 3,
+                new: 5,
+            })),
+            debugger.resume()
+        );
+        assert_eq!(Ok(Stop::Halted), debugger.resume());
+    }
+
+    #[test]
+    fn single_step() {
+        let computer = Computer::new("109,5,204,-2,99").unwrap();
+        let mut debugger = Debugger::new(computer);
+
+        assert_eq!(Ok(Stop::Step), debugger.step());
+        assert_eq!(
+            Registers {
+                ip: 2,
+                relative_base: 5
+            },
+            debugger.registers()
+        );
+        assert_eq!(Ok(Stop::Step), debugger.step());
+        assert_eq!(vec![-2], debugger.take_output());
+        assert_eq!(Ok(Stop::Halted), debugger.step());
+    }
+
+    #[test]
+    fn step_back() {
+        let computer = Computer::new("1101,1,2,9,4,9,104,7,99,0").unwrap();
+        let mut debugger = Debugger::new(computer);
+
+        assert_eq!(Ok(Stop::Halted), debugger.resume());
+        assert_eq!(vec![3, 7], debugger.take_output());
+        assert_eq!(4, debugger.history_len());
+        assert_eq!(Ok(Stop::Halted), debugger.step());
+        assert_eq!(4, debugger.history_len());
+
+        while debugger.step_back() {}
+
+        assert_eq!(0, debugger.registers().ip);
+        assert_eq!(vec![0], debugger.memory(9, 1));
+        assert!(!debugger.computer().halted());
+
+        assert_eq!(Ok(Stop::Halted), debugger.resume());
+        assert_eq!(vec![3, 7], debugger.take_output());
+    }
+
+    #[test]
+    fn reverse_to_write() {
+        let program = "1101,1,2,20,1101,5,5,21,1001,20,40,20,104,0,99";
+        let mut debugger = Debugger::new(Computer::new(program).unwrap());
+
+        assert_eq!(Ok(Stop::Halted), debugger.resume());
+        assert_eq!(
+            Some(MemoryWrite {
+                address: 20,
+                old: 3,
+                new: 43,
+            }),
+            debugger.reverse_to_write(20)
+        );
+        assert_eq!(8, debugger.registers().ip);
+        assert_eq!(vec![3, 10], debugger.memory(20, 2));
+
+        assert_eq!(
+            Some(MemoryWrite {
+                address: 20,
+                old: 0,
+                new: 3,
+            }),
+            debugger.reverse_to_write(20)
+        );
+        assert_eq!(0, debugger.registers().ip);
+        assert_eq!(None, debugger.reverse_to_write(21));
+        assert_eq!(0, debugger.registers().ip);
+    }
+
+    #[test]
+    fn reverse_to_output() {
+        let computer = Computer::new("3,9,4,9,3,9,4,9,99,0").unwrap();
+        let mut debugger = Debugger::new(computer);
+
+        debugger.provide_input(5);
+        debugger.provide_input(6);
+
+        assert_eq!(Ok(Stop::Halted), debugger.resume());
+        assert_eq!(vec![5, 6], debugger.take_output());
+
+        assert_eq!(Some(6), debugger.reverse_to_output());
+        assert_eq!(6, debugger.registers().ip);
+        assert_eq!(Ok(Stop::Step), debugger.step());
+        assert_eq!(Some(6), debugger.reverse_to_output());
+
+        assert!(debugger.step_back());
+        assert_eq!(vec![5], debugger.memory(9, 1));
+        assert_eq!(Ok(Stop::Halted), debugger.resume());
+        assert_eq!(vec![6], debugger.take_output());
+    }
+
+    #[test]
+    fn history_limit() {
+        let computer = Computer::new("1101,1,2,9,4,9,104,7,99,0").unwrap();
+        let mut debugger = Debugger::new(computer).history_limit(2);
+
+        assert_eq!(Ok(Stop::Halted), debugger.resume());
+        assert_eq!(2, debugger.history_len());
+        assert!(debugger.step_back());
+        assert!(debugger.step_back());
+        assert!(!debugger.step_back());
+        assert_eq!(6, debugger.registers().ip);
+
+        debugger.set_history_limit(0);
+
+        assert_eq!(Ok(Stop::Halted), debugger.resume());
+        assert_eq!(0, debugger.history_len());
+    }
+}
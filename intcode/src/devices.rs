@@ -0,0 +1,327 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Closed, Device, ValueType};
+
+#[derive(Default)]
+pub struct QueueIo {
+    input: VecDeque<ValueType>,
+    output: VecDeque<ValueType>,
+}
+
+impl QueueIo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_input(input: &[ValueType]) -> Self {
+        Self {
+            input: input.iter().cloned().collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn push_input(&mut self, value: ValueType) {
+        self.input.push_back(value);
+    }
+
+    pub fn pop_output(&mut self) -> Option<ValueType> {
+        self.output.pop_front()
+    }
+
+    pub fn take_output(&mut self) -> Vec<ValueType> {
+        self.output.drain(..).collect()
+    }
+}
+
+impl Device for QueueIo {
+    fn send(&mut self, value: ValueType) -> Result<(), Closed> {
+        self.output.push_back(value);
+
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Option<ValueType> {
+        self.input.pop_front()
+    }
+}
+
+pub struct FnIo<S, R>
+where
+    S: FnMut(ValueType) + Send,
+    R: FnMut() -> Option<ValueType> + Send,
+{
+    send: S,
+    receive: R,
+}
+
+impl<S, R> FnIo<S, R>
+where
+    S: FnMut(ValueType) + Send,
+    R: FnMut() -> Option<ValueType> + Send,
+{
+    pub fn new(send: S, receive: R) -> Self {
+        Self { send, receive }
+    }
+}
+
+impl<S, R> Device for FnIo<S, R>
+where
+    S: FnMut(ValueType) + Send,
+    R: FnMut() -> Option<ValueType> + Send,
+{
+    fn send(&mut self, value: ValueType) -> Result<(), Closed> {
+        (self.send)(value);
+
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Option<ValueType> {
+        (self.receive)()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    Input(ValueType),
+    Output(ValueType),
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Input(value) => write!(f, "in {}", value),
+            Event::Output(value) => write!(f, "out {}", value),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct EventParseError;
+
+impl fmt::Display for EventParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unable to parse io event")
+    }
+}
+
+impl Error for EventParseError {}
+
+impl FromStr for Event {
+    type Err = EventParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (direction, value) = s.trim().split_once(' ').ok_or(EventParseError)?;
+        let value = value.trim().parse().map_err(|_| EventParseError)?;
+
+        match direction {
+            "in" => Ok(Event::Input(value)),
+            "out" => Ok(Event::Output(value)),
+            _ => Err(EventParseError),
+        }
+    }
+}
+
+pub struct RecordingIo<D: Device> {
+    device: D,
+    events: Vec<Event>,
+}
+
+impl<D: Device> RecordingIo<D> {
+    pub fn new(device: D) -> Self {
+        Self {
+            device,
+            events: vec![],
+        }
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn device(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    pub fn into_inner(self) -> (D, Vec<Event>) {
+        (self.device, self.events)
+    }
+}
+
+impl<D: Device> Device for RecordingIo<D> {
+    fn send(&mut self, value: ValueType) -> Result<(), Closed> {
+        self.device.send(value)?;
+        self.events.push(Event::Output(value));
+
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Option<ValueType> {
+        let value = self.device.receive()?;
+        self.events.push(Event::Input(value));
+
+        Some(value)
+    }
+}
+
+pub struct ReplayIo {
+    events: VecDeque<Event>,
+    replayed: usize,
+}
+
+impl ReplayIo {
+    pub fn new(events: &[Event]) -> Self {
+        Self {
+            events: events.iter().cloned().collect(),
+            replayed: 0,
+        }
+    }
+
+    pub fn replayed(&self) -> usize {
+        self.replayed
+    }
+
+    pub fn finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+// A replay only advances while the program behaves exactly as recorded: an
+// unexpected output closes the output, and asking for input when the
+// recording expects an output closes the input
+impl Device for ReplayIo {
+    fn send(&mut self, value: ValueType) -> Result<(), Closed> {
+        match self.events.front() {
+            Some(&Event::Output(expected)) if expected == value => {
+                self.events.pop_front();
+                self.replayed += 1;
+
+                Ok(())
+            }
+            _ => Err(Closed),
+        }
+    }
+
+    fn receive(&mut self) -> Option<ValueType> {
+        match self.events.front() {
+            Some(&Event::Input(value)) => {
+                self.events.pop_front();
+                self.replayed += 1;
+
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Computer, ExecutionError, Registers};
+    use std::sync::{mpsc, Arc, Mutex};
+
+    const DOUBLER: &str = "3,11,1002,11,2,11,4,11,1105,1,0,0";
+
+    #[test]
+    fn queue() {
+        let io = Arc::new(Mutex::new(QueueIo::with_input(&[1, 2])));
+        let mut computer = Computer::new(DOUBLER).unwrap();
+
+        io.lock().unwrap().push_input(3);
+        computer.attach_device(Box::new(io.clone()));
+
+        assert!(matches!(
+            computer.run(),
+            Err(ExecutionError::InputClosed { .. })
+        ));
+        assert_eq!(vec![2, 4, 6], io.lock().unwrap().take_output());
+    }
+
+    #[test]
+    fn closures() {
+        let (sender, receiver) = mpsc::channel();
+        let mut inputs = vec![5, 4];
+        let mut computer = Computer::new(DOUBLER).unwrap();
+
+        computer.attach_device(Box::new(FnIo::new(
+            move |value| sender.send(value).unwrap(),
+            move || inputs.pop(),
+        )));
+
+        assert!(matches!(
+            computer.run(),
+            Err(ExecutionError::InputClosed { .. })
+        ));
+        assert_eq!(vec![8, 10], receiver.try_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let recording = Arc::new(Mutex::new(RecordingIo::new(QueueIo::with_input(&[7, 1]))));
+        let mut computer = Computer::new(DOUBLER).unwrap();
+
+        computer.attach_device(Box::new(recording.clone()));
+        assert!(matches!(
+            computer.run(),
+            Err(ExecutionError::InputClosed { .. })
+        ));
+
+        let events = recording.lock().unwrap().events().to_vec();
+
+        assert_eq!(
+            vec![
+                Event::Input(7),
+                Event::Output(14),
+                Event::Input(1),
+                Event::Output(2)
+            ],
+            events
+        );
+
+        let replay = Arc::new(Mutex::new(ReplayIo::new(&events)));
+
+        computer.attach_device(Box::new(replay.clone()));
+        assert!(matches!(
+            computer.run(),
+            Err(ExecutionError::InputClosed { .. })
+        ));
+
+        assert!(replay.lock().unwrap().finished());
+
+        let replay = Arc::new(Mutex::new(ReplayIo::new(&events)));
+        let mut computer = Computer::new("3,11,1002,11,3,11,4,11,1105,1,0,0").unwrap();
+
+        computer.attach_device(Box::new(replay.clone()));
+
+        assert_eq!(
+            Err(ExecutionError::OutputClosed {
+                value: 21,
+                registers: Registers {
+                    ip: 6,
+                    relative_base: 0
+                }
+            }),
+            computer.run()
+        );
+        assert_eq!(1, replay.lock().unwrap().replayed());
+    }
+
+    #[test]
+    fn event_text() {
+        let events = [Event::Input(-3), Event::Output(12)];
+        let text = events
+            .iter()
+            .map(|event| event.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec!["in -3", "out 12"], text);
+        assert_eq!(
+            Ok(events.to_vec()),
+            text.iter().map(|line| line.parse()).collect()
+        );
+        assert_eq!(Err(EventParseError), "sideways 3".parse::<Event>());
+    }
+}
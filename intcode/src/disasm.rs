@@ -0,0 +1,305 @@
+use std::fmt;
+
+use super::{Instruction, Opcode, Parameter, ParameterMode, Program, ValueType};
+
+const DATA_PER_LINE: usize = 8;
+
+pub(crate) struct Decoded {
+    pub(crate) opcode: Opcode,
+    pub(crate) parameters: Vec<Parameter>,
+    encoding: ValueType,
+}
+
+impl Decoded {
+    pub(crate) fn at(values: &[ValueType], address: usize) -> Option<Self> {
+        let opcode = *values.get(address)?;
+        let instruction = Opcode::parse(opcode).ok()?;
+        let operation = instruction.opcode()?;
+        let arguments = values.get(address + 1..address + instruction.len())?;
+
+        Some(Self {
+            opcode: operation,
+            parameters: arguments
+                .iter()
+                .zip(instruction.modes().iter())
+                .map(|(&value, &mode)| Parameter::new(value, mode))
+                .collect(),
+            encoding: opcode,
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        1 + self.parameters.len()
+    }
+
+    pub(crate) fn successors(&self, address: usize) -> Vec<usize> {
+        let next = address + self.len();
+
+        match self.opcode {
+            Opcode::Halt => vec![],
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let condition = self.parameters[0];
+                let target = self.parameters[1];
+
+                let taken = match condition.mode {
+                    ParameterMode::Immediate => {
+                        Some((condition.value != 0) == (self.opcode == Opcode::JumpIfTrue))
+                    }
+                    _ => None,
+                };
+
+                let mut successors = vec![];
+
+                if taken != Some(true) {
+                    successors.push(next);
+                }
+
+                if taken != Some(false)
+                    && target.mode == ParameterMode::Immediate
+                    && target.value >= 0
+                {
+                    successors.push(target.value as usize);
+                }
+
+                successors
+            }
+            Opcode::Add | Opcode::Multiply => {
+                let mut successors = vec![next];
+
+                // Calls push their return address onto the stack as a constant
+                if let [a, b, destination] = self.parameters[..] {
+                    if a.mode == ParameterMode::Immediate
+                        && b.mode == ParameterMode::Immediate
+                        && destination.mode == ParameterMode::Relative
+                    {
+                        let value = if self.opcode == Opcode::Add {
+                            a.value.checked_add(b.value)
+                        } else {
+                            a.value.checked_mul(b.value)
+                        };
+
+                        if let Some(value) = value.filter(|&value| value >= 0) {
+                            successors.push(value as usize);
+                        }
+                    }
+                }
+
+                successors
+            }
+            _ => vec![next],
+        }
+    }
+}
+
+impl fmt::Display for Decoded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.opcode.mnemonic())?;
+
+        // Redundant mode digits are kept, so that the listing assembles back
+        // into the same program
+        let modes = self
+            .parameters
+            .iter()
+            .map(|parameter| parameter.mode)
+            .collect::<Vec<_>>();
+
+        if Instruction::new(self.opcode, &modes).encode() != self.encoding {
+            write!(f, "({})", self.encoding)?;
+        }
+
+        for (i, parameter) in self.parameters.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, parameter)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn reachable(values: &[ValueType]) -> Vec<bool> {
+    let mut reached = vec![false; values.len()];
+    let mut pending = vec![0];
+
+    while let Some(address) = pending.pop() {
+        if address >= values.len() || reached[address] {
+            continue;
+        }
+
+        if let Some(decoded) = Decoded::at(values, address) {
+            reached[address] = true;
+            pending.extend(decoded.successors(address));
+        }
+    }
+
+    reached
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub address: usize,
+    pub values: Vec<ValueType>,
+    pub text: String,
+    pub code: bool,
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.code {
+            let raw = self
+                .values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>();
+
+            write!(
+                f,
+                "{:>5}: {:<32}; {}",
+                self.address,
+                self.text,
+                raw.join(",")
+            )
+        } else {
+            write!(f, "{:>5}: {}", self.address, self.text)
+        }
+    }
+}
+
+pub fn instruction(values: &[ValueType], address: usize) -> Option<Line> {
+    Decoded::at(values, address).map(|decoded| Line {
+        address,
+        values: values[address..address + decoded.len()].to_vec(),
+        text: decoded.to_string(),
+        code: true,
+    })
+}
+
+pub fn disassemble(program: &Program) -> Vec<Line> {
+    let values = program.values();
+    let reached = reachable(values);
+
+    let mut lines = vec![];
+    let mut address = 0;
+
+    while address < values.len() {
+        if let Some(line) = Some(address)
+            .filter(|&address| reached[address])
+            .and_then(|address| instruction(values, address))
+        {
+            address += line.values.len();
+            lines.push(line);
+        } else {
+            let end = (address + 1..values.len())
+                .take(DATA_PER_LINE - 1)
+                .find(|&address| reached[address])
+                .unwrap_or_else(|| (address + DATA_PER_LINE).min(values.len()));
+
+            let data = &values[address..end];
+
+            lines.push(Line {
+                address,
+                values: data.to_vec(),
+                text: format!(
+                    "DATA {}",
+                    data.iter()
+                        .map(|value| value.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                code: false,
+            });
+
+            address = end;
+        }
+    }
+
+    lines
+}
+
+pub fn listing(program: &Program) -> String {
+    disassemble(program)
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parameter_modes() {
+        let program = "1002,4,3,4,33".parse::<Program>().unwrap();
+
+        assert_eq!(
+            "    0: MUL [4], #3, [4]                ; 1002,4,3,4\n    \
+             4: DATA 33\n",
+            listing(&program)
+        );
+    }
+
+    #[test]
+    fn redundant_mode_digits() {
+        let program = "11104,7,10099".parse::<Program>().unwrap();
+
+        assert_eq!(
+            "    0: OUT(11104) #7                   ; 11104,7\n    \
+             2: HLT(10099)                      ; 10099\n",
+            listing(&program)
+        );
+    }
+
+    #[test]
+    fn relative_operands() {
+        let program = "109,1,204,-1,99".parse::<Program>().unwrap();
+
+        let lines = disassemble(&program)
+            .iter()
+            .map(|line| line.text.clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec!["ARB #1", "OUT rb-1", "HLT"], lines);
+    }
+
+    #[test]
+    fn data_after_unconditional_jump() {
+        let program = "1105,1,7,1,2,3,4,3,0,4,0,99".parse::<Program>().unwrap();
+
+        let lines = disassemble(&program)
+            .iter()
+            .map(|line| (line.address, line.text.clone()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                (0, "JT #1, #7".to_string()),
+                (3, "DATA 1, 2, 3, 4".to_string()),
+                (7, "IN [0]".to_string()),
+                (9, "OUT [0]".to_string()),
+                (11, "HLT".to_string()),
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn return_address_on_stack() {
+        let program = "109,20,21101,9,0,0,1105,1,10,99,2106,0,0"
+            .parse::<Program>()
+            .unwrap();
+
+        let lines = disassemble(&program)
+            .iter()
+            .map(|line| line.text.clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                "ARB #20",
+                "ADD #9, #0, rb+0",
+                "JT #1, #10",
+                "HLT",
+                "JF #0, rb+0"
+            ],
+            lines
+        );
+    }
+}
@@ -0,0 +1,408 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+use super::{Computer, ExecutionError, State, ValueType};
+
+const TIME_SLICE: usize = 1024;
+
+#[derive(Default)]
+struct Shared {
+    queue: VecDeque<ValueType>,
+    waker: Option<Waker>,
+    senders: usize,
+    receiver: bool,
+}
+
+pub struct Sender {
+    shared: Arc<Mutex<Shared>>,
+}
+
+pub struct Receiver {
+    shared: Arc<Mutex<Shared>>,
+}
+
+pub fn channel() -> (Sender, Receiver) {
+    let shared = Arc::new(Mutex::new(Shared {
+        senders: 1,
+        receiver: true,
+        ..Shared::default()
+    }));
+
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+impl Sender {
+    pub fn send(&self, value: ValueType) -> Result<(), ValueType> {
+        let mut shared = self.shared.lock().unwrap();
+
+        if !shared.receiver {
+            return Err(value);
+        }
+
+        shared.queue.push_back(value);
+
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+}
+
+impl Clone for Sender {
+    fn clone(&self) -> Self {
+        self.shared.lock().unwrap().senders += 1;
+
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap();
+
+        shared.senders -= 1;
+
+        if shared.senders == 0 {
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Receiver {
+    pub fn try_recv(&mut self) -> Option<ValueType> {
+        self.shared.lock().unwrap().queue.pop_front()
+    }
+
+    pub fn recv(&mut self) -> Recv<'_> {
+        Recv { receiver: self }
+    }
+
+    pub fn drain(&mut self) -> Vec<ValueType> {
+        self.shared.lock().unwrap().queue.drain(..).collect()
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context) -> Poll<Option<ValueType>> {
+        let mut shared = self.shared.lock().unwrap();
+
+        match shared.queue.pop_front() {
+            Some(value) => Poll::Ready(Some(value)),
+            None if shared.senders == 0 => Poll::Ready(None),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        self.shared.lock().unwrap().receiver = false;
+    }
+}
+
+pub struct Recv<'a> {
+    receiver: &'a mut Receiver,
+}
+
+impl Future for Recv<'_> {
+    type Output = Option<ValueType>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.get_mut().receiver.poll_recv(cx)
+    }
+}
+
+pub struct Machine {
+    computer: Computer,
+    input: Receiver,
+    output: Sender,
+}
+
+impl Machine {
+    pub fn new(computer: Computer) -> (Self, Sender, Receiver) {
+        let (sender, input) = channel();
+        let (output, receiver) = channel();
+
+        (
+            Self::with_channels(computer, input, output),
+            sender,
+            receiver,
+        )
+    }
+
+    pub fn with_channels(computer: Computer, input: Receiver, output: Sender) -> Self {
+        Self {
+            computer,
+            input,
+            output,
+        }
+    }
+
+    pub fn computer(&mut self) -> &mut Computer {
+        &mut self.computer
+    }
+}
+
+// A machine runs until it starves for input, yielding to other tasks every
+// TIME_SLICE instructions so that busy machines cannot monopolize a thread
+impl Future for Machine {
+    type Output = Result<(), ExecutionError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let Machine {
+            computer,
+            input,
+            output,
+        } = self.get_mut();
+
+        let result = computer.traced(|computer| {
+            for _ in 0..TIME_SLICE {
+                let registers = computer.registers();
+
+                match computer.advance()? {
+                    State::Running => (),
+                    State::Halted => return Ok(Poll::Ready(())),
+                    State::NeedsInput => match input.poll_recv(cx) {
+                        Poll::Ready(Some(value)) => computer.provide_input(value),
+                        Poll::Ready(None) => return Err(ExecutionError::InputClosed { registers }),
+                        Poll::Pending => return Ok(Poll::Pending),
+                    },
+                    State::Output(value) => output
+                        .send(value)
+                        .map_err(|value| ExecutionError::OutputClosed { value, registers })?,
+                }
+            }
+
+            cx.waker().wake_by_ref();
+
+            Ok(Poll::Pending)
+        });
+
+        match result {
+            Ok(poll) => poll.map(Ok),
+            Err(error) => Poll::Ready(Err(error)),
+        }
+    }
+}
+
+struct Task {
+    woken: AtomicBool,
+    thread: Thread,
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::SeqCst);
+        self.thread.unpark();
+    }
+}
+
+type BoxedFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+
+pub struct Executor<T> {
+    tasks: Vec<BoxedFuture<T>>,
+}
+
+impl<T> Default for Executor<T> {
+    fn default() -> Self {
+        Self { tasks: vec![] }
+    }
+}
+
+impl<T> Executor<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self, future: impl Future<Output = T> + 'static) -> usize {
+        self.tasks.push(Box::pin(future));
+
+        self.tasks.len() - 1
+    }
+
+    pub fn run(self) -> Vec<T> {
+        let mut tasks = self
+            .tasks
+            .into_iter()
+            .map(|future| {
+                let task = Arc::new(Task {
+                    woken: AtomicBool::new(true),
+                    thread: thread::current(),
+                });
+
+                (Some(future), task)
+            })
+            .collect::<Vec<_>>();
+        let mut results = tasks.iter().map(|_| None).collect::<Vec<_>>();
+        let mut remaining = tasks.len();
+
+        while remaining > 0 {
+            let mut polled = false;
+
+            for (i, (future, task)) in tasks.iter_mut().enumerate() {
+                if future.is_none() || !task.woken.swap(false, Ordering::SeqCst) {
+                    continue;
+                }
+
+                polled = true;
+
+                let waker = Waker::from(task.clone());
+                let mut cx = Context::from_waker(&waker);
+
+                if let Some(Poll::Ready(result)) =
+                    future.as_mut().map(|future| future.as_mut().poll(&mut cx))
+                {
+                    results[i] = Some(result);
+                    *future = None;
+                    remaining -= 1;
+                }
+            }
+
+            if !polled {
+                thread::park();
+            }
+        }
+
+        results.into_iter().map(Option::unwrap).collect()
+    }
+}
+
+pub fn block_on<T: 'static>(future: impl Future<Output = T> + 'static) -> T {
+    let mut executor = Executor::new();
+
+    executor.spawn(future);
+    executor.run().pop().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Registers;
+
+    #[test]
+    fn machine() {
+        let computer = Computer::new("3,9,1001,9,1,9,4,9,99,0").unwrap();
+        let (machine, sender, mut receiver) = Machine::new(computer);
+
+        sender.send(41).unwrap();
+
+        assert_eq!(Ok(()), block_on(machine));
+        assert_eq!(Some(42), receiver.try_recv());
+    }
+
+    #[test]
+    fn input_closed() {
+        let computer = Computer::new("3,0,99").unwrap();
+        let (machine, sender, _receiver) = Machine::new(computer);
+
+        drop(sender);
+
+        assert!(matches!(
+            block_on(machine),
+            Err(ExecutionError::InputClosed { .. })
+        ));
+    }
+
+    #[test]
+    fn output_closed() {
+        let computer = Computer::new("104,7,99").unwrap();
+        let (machine, _sender, receiver) = Machine::new(computer);
+
+        drop(receiver);
+
+        assert_eq!(
+            Err(ExecutionError::OutputClosed {
+                value: 7,
+                registers: Registers {
+                    ip: 0,
+                    relative_base: 0
+                }
+            }),
+            block_on(machine)
+        );
+    }
+
+    #[test]
+    fn feedback_loop() {
+        let program = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,\
+                       27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+        let phases = [9, 8, 7, 6, 5];
+
+        let (senders, receivers): (Vec<_>, Vec<_>) = phases.iter().map(|_| channel()).unzip();
+
+        for (sender, &phase) in senders.iter().zip(phases.iter()) {
+            sender.send(phase).unwrap();
+        }
+
+        senders[0].send(0).unwrap();
+
+        let (tap, mut tapped) = channel();
+        let mut outputs = senders[1..].to_vec();
+        outputs.push(tap);
+
+        let feedback = senders.into_iter().next().unwrap();
+        let mut executor = Executor::new();
+
+        for (input, output) in receivers.into_iter().zip(outputs) {
+            let computer = Computer::new(program).unwrap();
+            let machine = Machine::with_channels(computer, input, output);
+
+            executor.spawn(async move { machine.await.map(|_| None) });
+        }
+
+        executor.spawn(async move {
+            let mut last = None;
+
+            while let Some(value) = tapped.recv().await {
+                last = Some(value);
+                feedback.send(value).unwrap_or(());
+            }
+
+            Ok(last)
+        });
+
+        assert_eq!(Some(Ok(Some(139_629_729))), executor.run().pop());
+    }
+
+    #[test]
+    fn many_machines() {
+        let mut executor = Executor::new();
+        let mut receivers = vec![];
+
+        for i in 0..1000 {
+            let computer = Computer::new("3,9,1001,9,1,9,4,9,99,0").unwrap();
+            let (machine, sender, receiver) = Machine::new(computer);
+
+            sender.send(i).unwrap();
+            executor.spawn(machine);
+            receivers.push(receiver);
+        }
+
+        assert_eq!(1000, executor.run().len());
+        assert!(receivers
+            .iter_mut()
+            .enumerate()
+            .all(|(i, receiver)| receiver.try_recv() == Some(i as ValueType + 1)));
+    }
+}
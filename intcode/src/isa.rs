@@ -0,0 +1,365 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use super::{
+    DecodeError, ExecutionError, Instruction, Memory, Opcode, Operation, Parameter, Registers,
+    State, ValueType,
+};
+
+const OPCODES: usize = 100;
+const MAX_ARITY: usize = 3;
+
+pub type Handler = Arc<dyn Fn(&mut Context) -> Result<State, ExecutionError> + Send + Sync>;
+
+#[derive(Clone, Copy)]
+struct Definition {
+    operation: Operation,
+    arity: usize,
+    writes: u8,
+}
+
+#[derive(Clone)]
+pub struct InstructionSet {
+    definitions: Vec<Option<Definition>>,
+    handlers: Vec<Option<Handler>>,
+}
+
+impl Default for InstructionSet {
+    fn default() -> Self {
+        let mut definitions = vec![None; OPCODES];
+
+        for &opcode in Opcode::ALL.iter() {
+            definitions[opcode as usize] = Some(Definition {
+                operation: Operation::Builtin(opcode),
+                arity: opcode.arity(),
+                writes: opcode.writes(),
+            });
+        }
+
+        Self {
+            definitions,
+            handlers: vec![None; OPCODES],
+        }
+    }
+}
+
+impl InstructionSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Defining an opcode that already exists replaces it, built-ins included.
+    // Parameters listed in writes are shown as addresses in traces. Opcode 0
+    // is reserved, since it is what execution finds in unused memory.
+    pub fn define<F>(
+        mut self,
+        opcode: ValueType,
+        arity: usize,
+        writes: &[usize],
+        handler: F,
+    ) -> Self
+    where
+        F: Fn(&mut Context) -> Result<State, ExecutionError> + Send + Sync + 'static,
+    {
+        assert!(
+            (1..OPCODES as ValueType).contains(&opcode),
+            "opcode {} is not in 1..{}",
+            opcode,
+            OPCODES
+        );
+        assert!(
+            arity <= MAX_ARITY,
+            "opcode {} takes more than {} parameters",
+            opcode,
+            MAX_ARITY
+        );
+        assert!(
+            writes.iter().all(|&parameter| parameter < arity),
+            "opcode {} writes to a parameter it does not take",
+            opcode
+        );
+
+        self.handlers[opcode as usize] = Some(Arc::new(handler));
+        self.definitions[opcode as usize] = Some(Definition {
+            operation: Operation::Custom(opcode as usize),
+            arity,
+            writes: writes
+                .iter()
+                .fold(0, |writes, &parameter| writes | 1 << parameter),
+        });
+
+        self
+    }
+
+    pub fn remove(mut self, opcode: ValueType) -> Self {
+        if let Some(opcode) = usize::try_from(opcode)
+            .ok()
+            .filter(|&opcode| opcode < OPCODES)
+        {
+            self.definitions[opcode] = None;
+            self.handlers[opcode] = None;
+        }
+
+        self
+    }
+
+    pub fn contains(&self, opcode: ValueType) -> bool {
+        self.definition(opcode).is_some()
+    }
+
+    fn definition(&self, opcode: ValueType) -> Option<Definition> {
+        usize::try_from(opcode % OPCODES as ValueType)
+            .ok()
+            .and_then(|opcode| self.definitions[opcode])
+    }
+
+    pub(crate) fn decode(&self, opcode: ValueType) -> Result<Instruction, DecodeError> {
+        let definition = self.definition(opcode).ok_or(DecodeError::InvalidOpcode)?;
+
+        Instruction::parse(
+            opcode,
+            definition.operation,
+            definition.arity,
+            definition.writes,
+        )
+    }
+
+    // Custom definitions always have a handler in the slot of their opcode
+    pub(crate) fn handler(&self, opcode: usize) -> &Handler {
+        self.handlers[opcode].as_ref().unwrap()
+    }
+}
+
+// Handlers see their parameters through a context. Returning NeedsInput
+// retries the instruction once input arrives, so a handler should only do
+// that before it has consumed input or written memory.
+pub struct Context<'a> {
+    pub(crate) parameters: &'a [Parameter],
+    pub(crate) memory: &'a mut Memory,
+    pub(crate) inputs: &'a mut VecDeque<ValueType>,
+}
+
+impl Context<'_> {
+    pub fn arity(&self) -> usize {
+        self.parameters.len()
+    }
+
+    pub fn get(&self, parameter: usize) -> Result<ValueType, ExecutionError> {
+        self.memory.get(self.parameter(parameter)?)
+    }
+
+    pub fn set(&mut self, parameter: usize, value: ValueType) -> Result<(), ExecutionError> {
+        let parameter = self.parameter(parameter)?;
+
+        self.memory.set(parameter, value)
+    }
+
+    fn parameter(&self, parameter: usize) -> Result<Parameter, ExecutionError> {
+        self.parameters
+            .get(parameter)
+            .cloned()
+            .ok_or(ExecutionError::MissingParameter {
+                parameter,
+                registers: self.registers(),
+            })
+    }
+
+    pub fn read(&self, address: ValueType) -> Result<ValueType, ExecutionError> {
+        self.memory.read(address)
+    }
+
+    pub fn jump(&mut self, address: ValueType) -> Result<(), ExecutionError> {
+        self.memory.jump(address)
+    }
+
+    pub fn input(&mut self) -> Option<ValueType> {
+        self.inputs.pop_front()
+    }
+
+    pub fn registers(&self) -> Registers {
+        self.memory.registers()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::QueueIo;
+    use crate::Computer;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Mutex;
+
+    fn run(computer: &mut Computer, input: &[ValueType]) -> Vec<ValueType> {
+        let io = Arc::new(Mutex::new(QueueIo::with_input(input)));
+
+        computer.attach_device(Box::new(io.clone()));
+        computer.run().unwrap();
+
+        let output = io.lock().unwrap().take_output();
+        output
+    }
+
+    #[test]
+    fn custom_opcode() {
+        let square = InstructionSet::new().define(10, 2, &[1], |context| {
+            let value = context.get(0)?;
+            context.set(1, value * value)?;
+
+            Ok(State::Running)
+        });
+        let mut computer = Computer::new("110,7,9,4,9,99,0,0,0,0")
+            .unwrap()
+            .instruction_set(square);
+
+        assert_eq!(vec![49], run(&mut computer, &[]));
+    }
+
+    #[test]
+    fn debug_print() {
+        let printed = Arc::new(Mutex::new(vec![]));
+        let log = printed.clone();
+        let debug = InstructionSet::new().define(42, 1, &[], move |context| {
+            log.lock().unwrap().push(context.get(0)?);
+
+            Ok(State::Running)
+        });
+        let mut computer = Computer::new("3,9,42,9,142,-1,4,9,99,0")
+            .unwrap()
+            .instruction_set(debug);
+
+        assert_eq!(vec![5], run(&mut computer, &[5]));
+        assert_eq!(vec![5, -1], *printed.lock().unwrap());
+    }
+
+    #[test]
+    fn random_numbers() {
+        let seed = AtomicU64::new(1);
+        let random = InstructionSet::new().define(50, 1, &[0], move |context| {
+            let value = seed.fetch_add(1, Ordering::SeqCst) * 7 % 11;
+            context.set(0, value as ValueType)?;
+
+            Ok(State::Running)
+        });
+        let mut computer = Computer::new("50,9,4,9,50,9,4,9,99,0")
+            .unwrap()
+            .instruction_set(random);
+
+        assert_eq!(vec![7, 3], run(&mut computer, &[]));
+    }
+
+    #[test]
+    fn override_builtin() {
+        let shouting = InstructionSet::new()
+            .define(4, 1, &[], |context| Ok(State::Output(context.get(0)? * 10)));
+        let mut computer = Computer::new("104,4,104,2,99")
+            .unwrap()
+            .instruction_set(shouting);
+
+        assert_eq!(vec![40, 20], run(&mut computer, &[]));
+    }
+
+    #[test]
+    fn custom_input_and_jump() {
+        // 11 reads input and jumps to it
+        let jump = InstructionSet::new().define(11, 0, &[], |context| match context.input() {
+            Some(address) => {
+                context.jump(address)?;
+
+                Ok(State::Running)
+            }
+            None => Ok(State::NeedsInput),
+        });
+        let mut computer = Computer::new("11,104,1,99,104,2,99")
+            .unwrap()
+            .instruction_set(jump);
+
+        assert_eq!(vec![2], run(&mut computer, &[4]));
+    }
+
+    #[test]
+    fn removed_and_unknown_opcodes() {
+        let set = InstructionSet::new().remove(9);
+
+        assert!(!set.contains(9));
+        assert!(set.contains(1));
+        assert!(!set.contains(42));
+
+        let mut computer = Computer::new("109,1,99").unwrap().instruction_set(set);
+
+        assert!(matches!(
+            computer.run(),
+            Err(ExecutionError::InvalidOpcode { opcode: 109, .. })
+        ));
+    }
+
+    #[test]
+    fn redefining_opcodes() {
+        let set = (1..=3).fold(InstructionSet::new(), |set, value| {
+            set.define(20, 0, &[], move |_| Ok(State::Output(value)))
+        });
+
+        assert_eq!(1, set.handlers.iter().flatten().count());
+
+        let mut computer = Computer::new("20,99").unwrap().instruction_set(set);
+
+        assert_eq!(vec![3], run(&mut computer, &[]));
+    }
+
+    #[test]
+    #[should_panic(expected = "opcode 0 is not in 1..100")]
+    fn opcode_zero_reserved() {
+        InstructionSet::new().define(0, 0, &[], |_| Ok(State::Running));
+    }
+
+    #[test]
+    fn missing_parameters() {
+        let set = InstructionSet::new().define(12, 1, &[], |context| {
+            context.set(1, context.get(0)?)?;
+
+            Ok(State::Running)
+        });
+        let mut computer = Computer::new("112,5,99").unwrap().instruction_set(set);
+
+        assert_eq!(
+            Err(ExecutionError::MissingParameter {
+                parameter: 1,
+                registers: Registers {
+                    ip: 0,
+                    relative_base: 0
+                }
+            }),
+            computer.run()
+        );
+    }
+
+    #[test]
+    fn tracing_custom_opcodes() {
+        use crate::trace::{Trace, Tracer};
+
+        struct Operands(Vec<Vec<ValueType>>);
+
+        impl Tracer for Operands {
+            fn trace(&mut self, trace: &Trace) {
+                self.0.push(trace.operands.clone());
+            }
+        }
+
+        let set = InstructionSet::new().define(10, 2, &[1], |context| {
+            let value = context.get(0)?;
+            context.set(1, -value)?;
+
+            Ok(State::Running)
+        });
+        let recorder = Arc::new(Mutex::new(Operands(vec![])));
+        let mut computer = Computer::new("110,3,5,99,0,0")
+            .unwrap()
+            .instruction_set(set);
+
+        computer.attach_tracer(Box::new(recorder.clone()));
+        computer.run().unwrap();
+
+        assert_eq!(vec![3, 5], recorder.lock().unwrap().0[0]);
+        assert_eq!(-3, computer.memory()[5]);
+    }
+}
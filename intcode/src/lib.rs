@@ -1,13 +1,43 @@
+use std::collections::VecDeque;
+use std::convert::{TryFrom, TryInto};
 use std::error::Error;
 use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::mpsc;
 use std::sync::mpsc::TryRecvError;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use isa::InstructionSet;
+use network::NetworkError;
+use snapshot::MachineState;
+use trace::{Trace, Tracer};
+
+pub mod ascii;
+pub mod asm;
+pub mod cfg;
+pub mod coverage;
+pub mod debugger;
+pub mod devices;
+pub mod disasm;
+pub mod future;
+pub mod isa;
+pub mod network;
+pub mod profile;
+pub mod search;
+pub mod snapshot;
+pub mod symbolic;
+pub mod trace;
 
 const MEMORY_SIZE: usize = 4096;
+const MEMORY_LIMIT: usize = 1 << 24;
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
 pub type ValueType = i64;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 enum ParameterMode {
     Position = 0,
     Immediate = 1,
@@ -15,14 +45,14 @@ enum ParameterMode {
 }
 
 impl ParameterMode {
-    fn parse_nth_digit(value: ValueType, n: u32) -> ParameterMode {
+    fn parse_nth_digit(value: ValueType, n: u32) -> Option<ParameterMode> {
         let digit = value / (10 as ValueType).pow(n) % 10;
 
         match digit {
-            x if x == ParameterMode::Position as ValueType => ParameterMode::Position,
-            x if x == ParameterMode::Immediate as ValueType => ParameterMode::Immediate,
-            x if x == ParameterMode::Relative as ValueType => ParameterMode::Relative,
-            _ => ParameterMode::Position,
+            x if x == ParameterMode::Position as ValueType => Some(ParameterMode::Position),
+            x if x == ParameterMode::Immediate as ValueType => Some(ParameterMode::Immediate),
+            x if x == ParameterMode::Relative as ValueType => Some(ParameterMode::Relative),
+            _ => None,
         }
     }
 }
@@ -39,109 +69,564 @@ impl Parameter {
     }
 }
 
-#[derive(Debug, PartialEq)]
-struct Program {
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            ParameterMode::Position => write!(f, "[{}]", self.value),
+            ParameterMode::Immediate => write!(f, "#{}", self.value),
+            ParameterMode::Relative if self.value < 0 => write!(f, "rb{}", self.value),
+            ParameterMode::Relative => write!(f, "rb+{}", self.value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Program {
     values: Vec<ValueType>,
 }
 
 impl Program {
-    fn new(values: &[ValueType]) -> Self {
+    pub fn new(values: &[ValueType]) -> Self {
         Self {
             values: values.to_vec(),
         }
     }
+
+    pub fn values(&self) -> &[ValueType] {
+        &self.values
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Registers {
+    pub ip: usize,
+    pub relative_base: ValueType,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryWrite {
+    pub address: usize,
+    pub old: ValueType,
+    pub new: ValueType,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ExecutionError {
+    InvalidOpcode {
+        opcode: ValueType,
+        registers: Registers,
+    },
+    InvalidParameterMode {
+        opcode: ValueType,
+        registers: Registers,
+    },
+    NegativeAddress {
+        address: ValueType,
+        registers: Registers,
+    },
+    MemoryLimitExceeded {
+        address: ValueType,
+        limit: usize,
+        registers: Registers,
+    },
+    ImmediateWrite {
+        registers: Registers,
+    },
+    InputClosed {
+        registers: Registers,
+    },
+    OutputClosed {
+        value: ValueType,
+        registers: Registers,
+    },
+    BudgetExhausted {
+        instructions: u64,
+        registers: Registers,
+    },
+    Overflow {
+        registers: Registers,
+    },
+    MissingParameter {
+        parameter: usize,
+        registers: Registers,
+    },
+}
+
+impl ExecutionError {
+    pub fn registers(&self) -> Registers {
+        match *self {
+            ExecutionError::InvalidOpcode { registers, .. }
+            | ExecutionError::InvalidParameterMode { registers, .. }
+            | ExecutionError::NegativeAddress { registers, .. }
+            | ExecutionError::MemoryLimitExceeded { registers, .. }
+            | ExecutionError::ImmediateWrite { registers }
+            | ExecutionError::InputClosed { registers }
+            | ExecutionError::OutputClosed { registers, .. }
+            | ExecutionError::BudgetExhausted { registers, .. }
+            | ExecutionError::Overflow { registers }
+            | ExecutionError::MissingParameter { registers, .. } => registers,
+        }
+    }
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecutionError::InvalidOpcode { opcode, .. } => write!(f, "invalid opcode {}", opcode)?,
+            ExecutionError::InvalidParameterMode { opcode, .. } => {
+                write!(f, "invalid parameter mode in opcode {}", opcode)?
+            }
+            ExecutionError::NegativeAddress { address, .. } => {
+                write!(f, "access to negative address {}", address)?
+            }
+            ExecutionError::MemoryLimitExceeded { address, limit, .. } => write!(
+                f,
+                "access to address {} beyond memory limit {}",
+                address, limit
+            )?,
+            ExecutionError::ImmediateWrite { .. } => write!(f, "write in immediate mode")?,
+            ExecutionError::InputClosed { .. } => write!(f, "input closed")?,
+            ExecutionError::OutputClosed { value, .. } => {
+                write!(f, "output closed while sending {}", value)?
+            }
+            ExecutionError::BudgetExhausted { instructions, .. } => {
+                write!(f, "budget exhausted after {} instructions", instructions)?
+            }
+            ExecutionError::Overflow { .. } => write!(f, "arithmetic overflow")?,
+            ExecutionError::MissingParameter { parameter, .. } => {
+                write!(f, "access to missing parameter {}", parameter)?
+            }
+        }
+
+        let registers = self.registers();
+
+        write!(
+            f,
+            " at ip {} (relative base {})",
+            registers.ip, registers.relative_base
+        )
+    }
+}
+
+impl Error for ExecutionError {}
+
+enum DecodeError {
+    InvalidOpcode,
+    InvalidParameterMode,
+}
+
+impl DecodeError {
+    fn at(self, opcode: ValueType, registers: Registers) -> ExecutionError {
+        match self {
+            DecodeError::InvalidOpcode => ExecutionError::InvalidOpcode { opcode, registers },
+            DecodeError::InvalidParameterMode => {
+                ExecutionError::InvalidParameterMode { opcode, registers }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arithmetic {
+    Checked,
+    Wrapping,
+}
+
+impl Arithmetic {
+    fn add(self, a: ValueType, b: ValueType) -> Option<ValueType> {
+        match self {
+            Arithmetic::Checked => a.checked_add(b),
+            Arithmetic::Wrapping => Some(a.wrapping_add(b)),
+        }
+    }
+
+    fn multiply(self, a: ValueType, b: ValueType) -> Option<ValueType> {
+        match self {
+            Arithmetic::Checked => a.checked_mul(b),
+            Arithmetic::Wrapping => Some(a.wrapping_mul(b)),
+        }
+    }
 }
 
+#[derive(Clone)]
 struct Memory {
     values: Vec<ValueType>,
+    size: usize,
+    limit: usize,
     ip: usize,
+    next_ip: usize,
     relative_base: ValueType,
+    last_write: Option<MemoryWrite>,
+    decoded: Vec<Option<Instruction>>,
+    caching: bool,
+    arithmetic: Arithmetic,
 }
 
 impl Memory {
-    fn new(size: usize) -> Self {
+    fn new(size: usize, limit: usize) -> Self {
         Memory {
             values: vec![0; size],
+            size,
+            limit,
             ip: 0,
+            next_ip: 0,
             relative_base: 0,
+            last_write: None,
+            decoded: vec![],
+            caching: true,
+            arithmetic: Arithmetic::Checked,
         }
     }
 
-    fn load(&mut self, program: &Program) {
-        self.values.clear();
-        self.values.resize(self.values.capacity(), 0);
+    fn with_program(program: &Program, size: usize, limit: usize) -> Self {
+        let mut memory = Memory::new(size.max(program.values.len()), limit);
 
-        self.values
-            .splice(..program.values.len(), program.values.clone());
+        memory.values[..program.values.len()].copy_from_slice(&program.values);
+        memory.size = size;
 
+        memory
+    }
+
+    fn load(&mut self, program: &Program) -> Result<(), ExecutionError> {
         self.ip = 0;
         self.relative_base = 0;
+
+        self.values.clear();
+        self.values.resize(self.size, 0);
+
+        self.load_values(0, &program.values)
     }
 
-    fn load_values(&mut self, index: usize, values: &[ValueType]) {
-        self.values
-            .splice(index..index + values.len(), values.to_vec());
+    fn load_values(&mut self, index: usize, values: &[ValueType]) -> Result<(), ExecutionError> {
+        if let Some(last) = values.len().checked_sub(1) {
+            let last = index
+                .checked_add(last)
+                .and_then(|last| ValueType::try_from(last).ok())
+                .unwrap_or(ValueType::MAX);
+
+            self.grow(self.address(last)?);
+            self.values[index..index + values.len()].copy_from_slice(values);
+        }
+
+        self.decoded.clear();
+
+        Ok(())
     }
 
-    fn advance(&mut self, length: usize) -> &[ValueType] {
-        let values = &self.values[self.ip..self.ip + length];
+    fn grow(&mut self, address: usize) {
+        if address >= self.values.len() {
+            let size = (address + 1).max(self.values.len() * 2).min(self.limit);
 
-        self.ip += length;
+            self.values.resize(size, 0);
+        }
+    }
+
+    fn registers(&self) -> Registers {
+        Registers {
+            ip: self.ip,
+            relative_base: self.relative_base,
+        }
+    }
 
-        values
+    fn address(&self, address: ValueType) -> Result<usize, ExecutionError> {
+        if address < 0 {
+            Err(ExecutionError::NegativeAddress {
+                address,
+                registers: self.registers(),
+            })
+        } else if address as usize >= self.limit {
+            Err(ExecutionError::MemoryLimitExceeded {
+                address,
+                limit: self.limit,
+                registers: self.registers(),
+            })
+        } else {
+            Ok(address as usize)
+        }
     }
 
-    fn advance_relative_base(&mut self, amount: ValueType) {
-        self.relative_base += amount;
+    fn read(&self, address: ValueType) -> Result<ValueType, ExecutionError> {
+        Ok(self
+            .values
+            .get(self.address(address)?)
+            .cloned()
+            .unwrap_or(0))
     }
 
-    fn get(&self, parameter: Parameter) -> ValueType {
+    // Decoding only depends on the opcode value itself, so a write only has
+    // to invalidate the cache entry at the written address
+    fn decode(&mut self, instruction_set: &InstructionSet) -> Result<Instruction, ExecutionError> {
+        if let Some(&Some(instruction)) = self.decoded.get(self.ip) {
+            return Ok(instruction);
+        }
+
+        let opcode = self.read(self.ip as ValueType)?;
+        let instruction = instruction_set
+            .decode(opcode)
+            .map_err(|error| error.at(opcode, self.registers()))?;
+
+        // Only addresses that were executed are cached, so writing far out
+        // into memory does not grow the cache along with it
+        if self.caching {
+            if self.ip >= self.decoded.len() {
+                self.decoded.resize(self.ip + 1, None);
+            }
+
+            self.decoded[self.ip] = Some(instruction);
+        }
+
+        Ok(instruction)
+    }
+
+    fn invalidate(&mut self, address: usize) {
+        if let Some(instruction) = self.decoded.get_mut(address) {
+            *instruction = None;
+        }
+    }
+
+    fn parameters(&self, modes: &[ParameterMode]) -> Result<[Parameter; 3], ExecutionError> {
+        let mut parameters = [Parameter::new(0, ParameterMode::Position); 3];
+
+        for (i, &mode) in modes.iter().enumerate() {
+            parameters[i] = Parameter::new(self.read((self.ip + 1 + i) as ValueType)?, mode);
+        }
+
+        Ok(parameters)
+    }
+
+    fn add(&self, a: ValueType, b: ValueType) -> Result<ValueType, ExecutionError> {
+        self.arithmetic.add(a, b).ok_or(ExecutionError::Overflow {
+            registers: self.registers(),
+        })
+    }
+
+    fn multiply(&self, a: ValueType, b: ValueType) -> Result<ValueType, ExecutionError> {
+        self.arithmetic
+            .multiply(a, b)
+            .ok_or(ExecutionError::Overflow {
+                registers: self.registers(),
+            })
+    }
+
+    fn advance_relative_base(&mut self, amount: ValueType) -> Result<(), ExecutionError> {
+        self.relative_base = self.add(self.relative_base, amount)?;
+
+        Ok(())
+    }
+
+    fn get(&self, parameter: Parameter) -> Result<ValueType, ExecutionError> {
         match parameter.mode {
-            ParameterMode::Position => self.values[parameter.value as usize],
-            ParameterMode::Immediate => parameter.value,
-            ParameterMode::Relative => self.values[(self.relative_base + parameter.value) as usize],
+            ParameterMode::Position => self.read(parameter.value),
+            ParameterMode::Immediate => Ok(parameter.value),
+            ParameterMode::Relative => self.read(self.add(self.relative_base, parameter.value)?),
         }
     }
 
-    fn set(&mut self, parameter: Parameter, value: ValueType) {
-        let position = match parameter.mode {
-            ParameterMode::Position => &mut self.values[parameter.value as usize],
-            ParameterMode::Immediate => &mut self.values[parameter.value as usize],
+    fn set(&mut self, parameter: Parameter, value: ValueType) -> Result<(), ExecutionError> {
+        let address = match parameter.mode {
+            ParameterMode::Position => self.address(parameter.value)?,
+            ParameterMode::Immediate => {
+                return Err(ExecutionError::ImmediateWrite {
+                    registers: self.registers(),
+                })
+            }
             ParameterMode::Relative => {
-                &mut self.values[(self.relative_base + parameter.value) as usize]
+                self.address(self.add(self.relative_base, parameter.value)?)?
             }
         };
 
-        *position = value;
+        self.grow(address);
+
+        self.last_write = Some(MemoryWrite {
+            address,
+            old: self.values[address],
+            new: value,
+        });
+        self.values[address] = value;
+        self.invalidate(address);
+
+        Ok(())
     }
 
-    fn jump(&mut self, address: ValueType) {
-        self.ip = address as usize;
+    fn jump(&mut self, address: ValueType) -> Result<(), ExecutionError> {
+        if address < 0 {
+            return Err(ExecutionError::NegativeAddress {
+                address,
+                registers: self.registers(),
+            });
+        }
+
+        self.next_ip = address as usize;
+
+        Ok(())
     }
 }
 
 #[derive(Debug, PartialEq)]
-pub struct ProgramParseError;
+pub struct ProgramParseError {
+    pub index: usize,
+    pub token: String,
+}
 
 impl fmt::Display for ProgramParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "unable to parse program")
+        write!(
+            f,
+            "unable to parse program: invalid value {:?} at index {}",
+            self.token, self.index
+        )
     }
 }
 
 impl Error for ProgramParseError {}
 
+// Values are separated by commas and/or line breaks, surrounding whitespace
+// is ignored and # starts a comment that runs to the end of the line
 impl FromStr for Program {
     type Err = ProgramParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let values = s
-            .split(",")
-            .map(|value| value.parse::<ValueType>().map_err(|_| ProgramParseError))
+        let mut values = vec![];
+
+        for line in s.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            // A line may end with one separator, but fields may not be empty
+            for token in line.strip_suffix(',').unwrap_or(line).split(',') {
+                let token = token.trim();
+                let value = token.parse::<ValueType>().map_err(|_| ProgramParseError {
+                    index: values.len(),
+                    token: token.to_string(),
+                })?;
+
+                values.push(value);
+            }
+        }
+
+        if values.is_empty() {
+            return Err(ProgramParseError {
+                index: 0,
+                token: String::new(),
+            });
+        }
+
+        Ok(Program::new(&values))
+    }
+}
+
+impl Program {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        Ok(fs::read_to_string(path)?.parse()?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProgramParseError> {
+        let cells = bytes.chunks(8);
+        let index = cells.len().saturating_sub(1);
+
+        let values = cells
+            .map(|cell| {
+                cell.try_into()
+                    .map(ValueType::from_le_bytes)
+                    .map_err(|_| ProgramParseError {
+                        index,
+                        token: format!("{:02x?}", cell),
+                    })
+            })
             .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Program::new(&values))
     }
+
+    pub fn from_binary_file(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        Ok(Program::from_bytes(&fs::read(path)?)?)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse(ProgramParseError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(error) => error.fmt(f),
+            LoadError::Parse(error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(error: io::Error) -> Self {
+        LoadError::Io(error)
+    }
+}
+
+impl From<ProgramParseError> for LoadError {
+    fn from(error: ProgramParseError) -> Self {
+        LoadError::Parse(error)
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, value) in self.values.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { "" } else { "," }, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum IntcodeError {
+    Parse(ProgramParseError),
+    Execution(ExecutionError),
+    Network(NetworkError),
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntcodeError::Parse(error) => error.fmt(f),
+            IntcodeError::Execution(error) => error.fmt(f),
+            IntcodeError::Network(error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for IntcodeError {}
+
+impl From<ProgramParseError> for IntcodeError {
+    fn from(error: ProgramParseError) -> Self {
+        IntcodeError::Parse(error)
+    }
+}
+
+impl From<ExecutionError> for IntcodeError {
+    fn from(error: ExecutionError) -> Self {
+        IntcodeError::Execution(error)
+    }
+}
+
+impl From<NetworkError> for IntcodeError {
+    fn from(error: NetworkError) -> Self {
+        IntcodeError::Network(error)
+    }
 }
 
 pub trait Io: Send {
@@ -150,11 +635,86 @@ pub trait Io: Send {
     fn receive(&mut self) -> ValueType {
         0
     }
+
+    fn try_receive(&mut self) -> Option<ValueType> {
+        Some(self.receive())
+    }
+}
+
+impl<T: Io> Io for Arc<Mutex<T>> {
+    fn send(&mut self, value: ValueType) {
+        self.lock().unwrap().send(value);
+    }
+
+    fn receive(&mut self) -> ValueType {
+        self.lock().unwrap().receive()
+    }
+
+    fn try_receive(&mut self) -> Option<ValueType> {
+        self.lock().unwrap().try_receive()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Closed;
+
+impl fmt::Display for Closed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "device closed")
+    }
+}
+
+impl Error for Closed {}
+
+pub trait Device: Send {
+    fn send(&mut self, value: ValueType) -> Result<(), Closed>;
+
+    fn receive(&mut self) -> Option<ValueType>;
+}
+
+impl<T: Device> Device for Arc<Mutex<T>> {
+    fn send(&mut self, value: ValueType) -> Result<(), Closed> {
+        self.lock().unwrap().send(value)
+    }
+
+    fn receive(&mut self) -> Option<ValueType> {
+        self.lock().unwrap().receive()
+    }
+}
+
+pub struct IoAdapter {
+    io: Box<dyn Io>,
+}
+
+impl IoAdapter {
+    pub fn new(io: Box<dyn Io>) -> Self {
+        Self { io }
+    }
+}
+
+impl Device for IoAdapter {
+    fn send(&mut self, value: ValueType) -> Result<(), Closed> {
+        self.io.send(value);
+
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Option<ValueType> {
+        self.io.try_receive()
+    }
 }
 
 struct NullIo;
 
-impl Io for NullIo {}
+impl Device for NullIo {
+    fn send(&mut self, _value: ValueType) -> Result<(), Closed> {
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Option<ValueType> {
+        Some(0)
+    }
+}
 
 struct AsyncIo {
     input: mpsc::Receiver<ValueType>,
@@ -166,29 +726,22 @@ impl AsyncIo {
         let (sender, input) = mpsc::channel();
         let (output, receiver) = mpsc::channel();
 
-        (
-            Self {
-                input: input,
-                output: output,
-            },
-            sender,
-            receiver,
-        )
+        (Self { input, output }, sender, receiver)
     }
 }
 
-impl Io for AsyncIo {
-    fn send(&mut self, value: ValueType) {
-        self.output.send(value).unwrap_or(());
+impl Device for AsyncIo {
+    fn send(&mut self, value: ValueType) -> Result<(), Closed> {
+        self.output.send(value).map_err(|_| Closed)
     }
 
-    fn receive(&mut self) -> ValueType {
-        self.input.recv().unwrap()
+    fn receive(&mut self) -> Option<ValueType> {
+        self.input.recv().ok()
     }
 }
 
-#[derive(Clone, Copy)]
-enum Opcode {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Opcode {
     Add = 1,
     Multiply = 2,
     Input = 3,
@@ -201,128 +754,349 @@ enum Opcode {
     Halt = 99,
 }
 
-struct Operation {
-    parameter_modes: Vec<ParameterMode>,
-    operation: fn(&[Parameter], &mut Memory, &mut dyn Io),
-    halt: bool,
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum State {
+    Running,
+    Halted,
+    NeedsInput,
+    Output(ValueType),
+}
+
+type OperationFn =
+    fn(&[Parameter], &mut Memory, &mut VecDeque<ValueType>) -> Result<State, ExecutionError>;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Operation {
+    Builtin(Opcode),
+    Custom(usize),
+}
+
+// Write parameters are kept as a bit mask indexed by parameter position
+#[derive(Clone, Copy)]
+struct Instruction {
+    code: ValueType,
+    operation: Operation,
+    arity: usize,
+    writes: u8,
+    parameter_modes: [ParameterMode; 3],
 }
 
-impl Operation {
-    fn new(
-        operation: fn(&[Parameter], &mut Memory, &mut dyn Io),
-        parameter_modes: &[ParameterMode],
-    ) -> Self {
+impl Instruction {
+    fn new(opcode: Opcode, modes: &[ParameterMode]) -> Self {
+        let mut parameter_modes = [ParameterMode::Position; 3];
+        parameter_modes[..modes.len()].copy_from_slice(modes);
+
         Self {
-            parameter_modes: parameter_modes.to_vec(),
+            code: opcode as ValueType,
+            operation: Operation::Builtin(opcode),
+            arity: opcode.arity(),
+            writes: opcode.writes(),
+            parameter_modes,
+        }
+    }
+
+    fn parse(
+        opcode: ValueType,
+        operation: Operation,
+        arity: usize,
+        writes: u8,
+    ) -> Result<Self, DecodeError> {
+        let mut modes = [ParameterMode::Position; 3];
+
+        for (n, mode) in modes.iter_mut().enumerate().take(arity) {
+            *mode = ParameterMode::parse_nth_digit(opcode, 2 + n as u32)
+                .ok_or(DecodeError::InvalidParameterMode)?;
+        }
+
+        Ok(Self {
+            code: opcode % 100,
             operation,
-            halt: false,
+            arity,
+            writes,
+            parameter_modes: modes,
+        })
+    }
+
+    fn opcode(&self) -> Option<Opcode> {
+        match self.operation {
+            Operation::Builtin(opcode) => Some(opcode),
+            Operation::Custom(_) => None,
         }
     }
 
-    fn halt(mut self) -> Self {
-        self.halt = true;
+    fn modes(&self) -> &[ParameterMode] {
+        &self.parameter_modes[..self.arity]
+    }
+
+    fn len(&self) -> usize {
+        1 + self.arity
+    }
 
-        self
+    fn writes(&self, parameter: usize) -> bool {
+        self.writes & (1 << parameter) != 0
     }
 
-    fn execute(&self, memory: &mut Memory, io: &mut dyn Io) -> bool {
-        let parameters = memory
-            .advance(self.parameter_modes.len())
+    fn operands(&self, memory: &Memory) -> Result<Vec<ValueType>, ExecutionError> {
+        memory.parameters(self.modes())?[..self.modes().len()]
             .iter()
-            .zip(self.parameter_modes.iter())
-            .map(|(&value, &mode)| Parameter::new(value, mode))
-            .collect::<Vec<_>>();
+            .enumerate()
+            .map(|(i, parameter)| match parameter.mode {
+                ParameterMode::Relative if self.writes(i) => {
+                    memory.add(memory.relative_base, parameter.value)
+                }
+                _ if self.writes(i) => Ok(parameter.value),
+                _ => memory.get(*parameter),
+            })
+            .collect()
+    }
+
+    fn encode(&self) -> ValueType {
+        self.modes()
+            .iter()
+            .enumerate()
+            .fold(self.code, |opcode, (i, &mode)| {
+                opcode + mode as ValueType * (10 as ValueType).pow(2 + i as u32)
+            })
+    }
+
+    fn execute(
+        &self,
+        instruction_set: &InstructionSet,
+        memory: &mut Memory,
+        inputs: &mut VecDeque<ValueType>,
+    ) -> Result<State, ExecutionError> {
+        let modes = self.modes();
+        let parameters = memory.parameters(modes)?;
+        let parameters = &parameters[..modes.len()];
+
+        memory.last_write = None;
+        memory.next_ip = memory.ip + self.len();
+
+        let state = match self.operation {
+            Operation::Builtin(opcode) => (opcode.operation())(parameters, memory, inputs)?,
+            Operation::Custom(opcode) => instruction_set.handler(opcode)(&mut isa::Context {
+                parameters,
+                memory,
+                inputs,
+            })?,
+        };
 
-        (self.operation)(&parameters, memory, io);
+        match state {
+            State::Halted | State::NeedsInput => (),
+            State::Running | State::Output(_) => memory.ip = memory.next_ip,
+        }
 
-        !self.halt
+        Ok(state)
     }
 }
 
-fn add(parameters: &[Parameter], memory: &mut Memory, _io: &mut dyn Io) {
+fn add(
+    parameters: &[Parameter],
+    memory: &mut Memory,
+    _inputs: &mut VecDeque<ValueType>,
+) -> Result<State, ExecutionError> {
     memory.set(
         parameters[2],
-        memory.get(parameters[0]) + memory.get(parameters[1]),
-    );
+        memory.add(memory.get(parameters[0])?, memory.get(parameters[1])?)?,
+    )?;
+
+    Ok(State::Running)
 }
 
-fn multiply(parameters: &[Parameter], memory: &mut Memory, _io: &mut dyn Io) {
+fn multiply(
+    parameters: &[Parameter],
+    memory: &mut Memory,
+    _inputs: &mut VecDeque<ValueType>,
+) -> Result<State, ExecutionError> {
     memory.set(
         parameters[2],
-        memory.get(parameters[0]) * memory.get(parameters[1]),
-    );
+        memory.multiply(memory.get(parameters[0])?, memory.get(parameters[1])?)?,
+    )?;
+
+    Ok(State::Running)
 }
 
-fn input(parameters: &[Parameter], memory: &mut Memory, io: &mut dyn Io) {
-    memory.set(parameters[0], io.receive());
+fn input(
+    parameters: &[Parameter],
+    memory: &mut Memory,
+    inputs: &mut VecDeque<ValueType>,
+) -> Result<State, ExecutionError> {
+    match inputs.front() {
+        Some(&value) => {
+            memory.set(parameters[0], value)?;
+            inputs.pop_front();
+
+            Ok(State::Running)
+        }
+        None => Ok(State::NeedsInput),
+    }
 }
 
-fn output(parameters: &[Parameter], memory: &mut Memory, io: &mut dyn Io) {
-    io.send(memory.get(parameters[0]));
+fn output(
+    parameters: &[Parameter],
+    memory: &mut Memory,
+    _inputs: &mut VecDeque<ValueType>,
+) -> Result<State, ExecutionError> {
+    Ok(State::Output(memory.get(parameters[0])?))
 }
 
-fn jump_if_true(parameters: &[Parameter], memory: &mut Memory, _io: &mut dyn Io) {
-    if memory.get(parameters[0]) != 0 {
-        memory.jump(memory.get(parameters[1]));
+fn jump_if_true(
+    parameters: &[Parameter],
+    memory: &mut Memory,
+    _inputs: &mut VecDeque<ValueType>,
+) -> Result<State, ExecutionError> {
+    if memory.get(parameters[0])? != 0 {
+        memory.jump(memory.get(parameters[1])?)?;
     }
+
+    Ok(State::Running)
 }
 
-fn jump_if_false(parameters: &[Parameter], memory: &mut Memory, _io: &mut dyn Io) {
-    if memory.get(parameters[0]) == 0 {
-        memory.jump(memory.get(parameters[1]));
+fn jump_if_false(
+    parameters: &[Parameter],
+    memory: &mut Memory,
+    _inputs: &mut VecDeque<ValueType>,
+) -> Result<State, ExecutionError> {
+    if memory.get(parameters[0])? == 0 {
+        memory.jump(memory.get(parameters[1])?)?;
     }
+
+    Ok(State::Running)
 }
 
-fn less_than(parameters: &[Parameter], memory: &mut Memory, _io: &mut dyn Io) {
+fn less_than(
+    parameters: &[Parameter],
+    memory: &mut Memory,
+    _inputs: &mut VecDeque<ValueType>,
+) -> Result<State, ExecutionError> {
     memory.set(
         parameters[2],
-        if memory.get(parameters[0]) < memory.get(parameters[1]) {
+        if memory.get(parameters[0])? < memory.get(parameters[1])? {
             1
         } else {
             0
         },
-    );
+    )?;
+
+    Ok(State::Running)
 }
 
-fn equals(parameters: &[Parameter], memory: &mut Memory, _io: &mut dyn Io) {
+fn equals(
+    parameters: &[Parameter],
+    memory: &mut Memory,
+    _inputs: &mut VecDeque<ValueType>,
+) -> Result<State, ExecutionError> {
     memory.set(
         parameters[2],
-        if memory.get(parameters[0]) == memory.get(parameters[1]) {
+        if memory.get(parameters[0])? == memory.get(parameters[1])? {
             1
         } else {
             0
         },
-    );
+    )?;
+
+    Ok(State::Running)
 }
 
-fn adjust_relative_base(parameters: &[Parameter], memory: &mut Memory, _io: &mut dyn Io) {
-    memory.advance_relative_base(memory.get(parameters[0]));
+fn adjust_relative_base(
+    parameters: &[Parameter],
+    memory: &mut Memory,
+    _inputs: &mut VecDeque<ValueType>,
+) -> Result<State, ExecutionError> {
+    memory.advance_relative_base(memory.get(parameters[0])?)?;
+
+    Ok(State::Running)
 }
 
-fn nop(_parameters: &[Parameter], _memory: &mut Memory, _io: &mut dyn Io) {}
+fn halt(
+    _parameters: &[Parameter],
+    _memory: &mut Memory,
+    _inputs: &mut VecDeque<ValueType>,
+) -> Result<State, ExecutionError> {
+    Ok(State::Halted)
+}
 
 impl Opcode {
-    fn parse(opcode: ValueType) -> Operation {
-        let modes = (2..=4)
-            .map(|n| ParameterMode::parse_nth_digit(opcode, n))
-            .collect::<Vec<_>>();
+    const ALL: [Opcode; 10] = [
+        Opcode::Add,
+        Opcode::Multiply,
+        Opcode::Input,
+        Opcode::Output,
+        Opcode::JumpIfTrue,
+        Opcode::JumpIfFalse,
+        Opcode::LessThan,
+        Opcode::Equals,
+        Opcode::AdjustRelativeBase,
+        Opcode::Halt,
+    ];
+
+    fn parse(opcode: ValueType) -> Result<Instruction, DecodeError> {
+        let operation = match opcode % 100 {
+            x if x == Opcode::Add as ValueType => Opcode::Add,
+            x if x == Opcode::Multiply as ValueType => Opcode::Multiply,
+            x if x == Opcode::Input as ValueType => Opcode::Input,
+            x if x == Opcode::Output as ValueType => Opcode::Output,
+            x if x == Opcode::JumpIfTrue as ValueType => Opcode::JumpIfTrue,
+            x if x == Opcode::JumpIfFalse as ValueType => Opcode::JumpIfFalse,
+            x if x == Opcode::LessThan as ValueType => Opcode::LessThan,
+            x if x == Opcode::Equals as ValueType => Opcode::Equals,
+            x if x == Opcode::AdjustRelativeBase as ValueType => Opcode::AdjustRelativeBase,
+            x if x == Opcode::Halt as ValueType => Opcode::Halt,
+            _ => return Err(DecodeError::InvalidOpcode),
+        };
 
-        match opcode % 100 {
-            x if x == Opcode::Add as ValueType => Operation::new(add, &modes),
-            x if x == Opcode::Multiply as ValueType => Operation::new(multiply, &modes),
-            x if x == Opcode::Input as ValueType => Operation::new(input, &modes[0..1]),
-            x if x == Opcode::Output as ValueType => Operation::new(output, &modes[0..1]),
-            x if x == Opcode::JumpIfTrue as ValueType => Operation::new(jump_if_true, &modes[0..2]),
-            x if x == Opcode::JumpIfFalse as ValueType => {
-                Operation::new(jump_if_false, &modes[0..2])
-            }
-            x if x == Opcode::LessThan as ValueType => Operation::new(less_than, &modes[0..3]),
-            x if x == Opcode::Equals as ValueType => Operation::new(equals, &modes[0..3]),
-            x if x == Opcode::AdjustRelativeBase as ValueType => {
-                Operation::new(adjust_relative_base, &modes[0..1])
-            }
-            x if x == Opcode::Halt as ValueType => Operation::new(nop, &[]).halt(),
-            _ => Operation::new(nop, &[]),
+        Instruction::parse(
+            opcode,
+            Operation::Builtin(operation),
+            operation.arity(),
+            operation.writes(),
+        )
+    }
+
+    fn arity(self) -> usize {
+        match self {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => 3,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => 2,
+            Opcode::Input | Opcode::Output | Opcode::AdjustRelativeBase => 1,
+            Opcode::Halt => 0,
+        }
+    }
+
+    fn writes(self) -> u8 {
+        match self {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => 1 << 2,
+            Opcode::Input => 1,
+            _ => 0,
+        }
+    }
+
+    fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Add => "ADD",
+            Opcode::Multiply => "MUL",
+            Opcode::Input => "IN",
+            Opcode::Output => "OUT",
+            Opcode::JumpIfTrue => "JT",
+            Opcode::JumpIfFalse => "JF",
+            Opcode::LessThan => "LT",
+            Opcode::Equals => "EQ",
+            Opcode::AdjustRelativeBase => "ARB",
+            Opcode::Halt => "HLT",
+        }
+    }
+
+    fn operation(self) -> OperationFn {
+        match self {
+            Opcode::Add => add,
+            Opcode::Multiply => multiply,
+            Opcode::Input => input,
+            Opcode::Output => output,
+            Opcode::JumpIfTrue => jump_if_true,
+            Opcode::JumpIfFalse => jump_if_false,
+            Opcode::LessThan => less_than,
+            Opcode::Equals => equals,
+            Opcode::AdjustRelativeBase => adjust_relative_base,
+            Opcode::Halt => halt,
         }
     }
 }
@@ -330,71 +1104,424 @@ impl Opcode {
 pub struct Computer {
     program: Program,
     memory: Memory,
-    io: Box<dyn Io>,
+    inputs: VecDeque<ValueType>,
+    halted: bool,
+    instruction_set: Arc<InstructionSet>,
+    io: Box<dyn Device>,
+    tracer: Option<Box<dyn Tracer>>,
+    budget: Option<u64>,
+    time_limit: Option<Duration>,
+    executed: u64,
+    deadline: Option<Instant>,
     shutdown_button: mpsc::Sender<()>,
     shutdown_receiver: mpsc::Receiver<()>,
 }
 
 impl Computer {
     pub fn new(program: &str) -> Result<Self, ProgramParseError> {
+        Ok(Self::from_program(&program.parse()?))
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        Ok(Self::from_program(&Program::from_file(path)?))
+    }
+
+    pub fn from_program(program: &Program) -> Self {
         let (shutdown_button, shutdown_receiver) = mpsc::channel();
 
-        Ok(Self {
-            program: program.parse()?,
-            memory: Memory::new(MEMORY_SIZE),
+        Self {
+            memory: Memory::with_program(program, MEMORY_SIZE, MEMORY_LIMIT),
+            program: program.clone(),
+            inputs: VecDeque::new(),
+            halted: false,
+            instruction_set: Arc::new(InstructionSet::new()),
             io: Box::new(NullIo),
+            tracer: None,
+            budget: None,
+            time_limit: None,
+            executed: 0,
+            deadline: None,
             shutdown_button,
             shutdown_receiver,
-        })
+        }
+    }
+
+    pub fn memory_size(mut self, size: usize) -> Self {
+        self.memory.size = size;
+
+        if self.memory.values.len() < size {
+            self.memory.values.resize(size, 0);
+        }
+
+        self
+    }
+
+    pub fn memory_limit(mut self, limit: usize) -> Self {
+        self.memory.limit = limit;
+
+        self
+    }
+
+    pub fn decode_cache(mut self, enabled: bool) -> Self {
+        self.memory.caching = enabled;
+        self.memory.decoded.clear();
+
+        self
+    }
+
+    pub fn arithmetic(mut self, arithmetic: Arithmetic) -> Self {
+        self.memory.arithmetic = arithmetic;
+
+        self
+    }
+
+    pub fn instruction_set(mut self, instruction_set: InstructionSet) -> Self {
+        self.instruction_set = Arc::new(instruction_set);
+        self.memory.decoded.clear();
+
+        self
+    }
+
+    pub fn instruction_budget(mut self, budget: u64) -> Self {
+        self.budget = Some(budget);
+
+        self
+    }
+
+    pub fn time_limit(mut self, time_limit: Duration) -> Self {
+        self.time_limit = Some(time_limit);
+
+        self
+    }
+
+    pub fn attach_io(&mut self, io: Box<dyn Io>) {
+        self.io = Box::new(IoAdapter::new(io));
+    }
+
+    pub fn attach_device(&mut self, device: Box<dyn Device>) {
+        self.io = device;
+    }
+
+    pub fn attach_tracer(&mut self, tracer: Box<dyn Tracer>) {
+        self.tracer = Some(tracer);
+    }
+
+    pub fn detach_tracer(&mut self) -> Option<Box<dyn Tracer>> {
+        self.tracer.take()
+    }
+
+    pub fn get_io(&mut self) -> (mpsc::Sender<ValueType>, mpsc::Receiver<ValueType>) {
+        let (io, sender, receiver) = AsyncIo::new();
+
+        self.io = Box::new(io);
+
+        (sender, receiver)
+    }
+
+    pub fn dma(&mut self, position: usize) -> Result<&mut ValueType, ExecutionError> {
+        let position = self
+            .memory
+            .address(ValueType::try_from(position).unwrap_or(ValueType::MAX))?;
+
+        self.memory.grow(position);
+        self.memory.invalidate(position);
+
+        Ok(&mut self.memory.values[position])
+    }
+
+    pub fn shutdown_button(&self) -> mpsc::Sender<()> {
+        self.shutdown_button.clone()
+    }
+
+    pub fn registers(&self) -> Registers {
+        self.memory.registers()
+    }
+
+    pub fn memory(&self) -> &[ValueType] {
+        &self.memory.values
+    }
+
+    pub fn last_write(&self) -> Option<MemoryWrite> {
+        self.memory.last_write
+    }
+
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn snapshot(&self) -> MachineState {
+        let memory = &self.memory.values;
+        let used = memory
+            .iter()
+            .rposition(|&value| value != 0)
+            .map_or(0, |i| i + 1);
+
+        MachineState {
+            memory: memory[..used].to_vec(),
+            registers: self.memory.registers(),
+            halted: self.halted,
+            inputs: self.inputs.iter().cloned().collect(),
+        }
+    }
+
+    pub fn restore(&mut self, state: &MachineState) -> Result<(), ExecutionError> {
+        if state.memory.len() > self.memory.limit {
+            return Err(ExecutionError::MemoryLimitExceeded {
+                address: state.memory.len() as ValueType - 1,
+                limit: self.memory.limit,
+                registers: state.registers,
+            });
+        }
+
+        self.memory.values.clear();
+        self.memory.values.extend_from_slice(&state.memory);
+        self.memory
+            .values
+            .resize(self.memory.size.max(state.memory.len()), 0);
+
+        self.memory.ip = state.registers.ip;
+        self.memory.relative_base = state.registers.relative_base;
+        self.memory.last_write = None;
+        self.memory.decoded.clear();
+
+        self.halted = state.halted;
+        self.inputs = state.inputs.iter().cloned().collect();
+
+        Ok(())
+    }
+
+    // A fork gets its own shutdown button, so shutting down the original
+    // leaves its forks running and vice versa. Tracers are not carried over.
+    pub fn fork(&self, io: Box<dyn Device>) -> Self {
+        let (shutdown_button, shutdown_receiver) = mpsc::channel();
+
+        Self {
+            program: self.program.clone(),
+            memory: self.memory.clone(),
+            inputs: self.inputs.clone(),
+            halted: self.halted,
+            instruction_set: self.instruction_set.clone(),
+            io,
+            tracer: None,
+            budget: self.budget,
+            time_limit: self.time_limit,
+            executed: 0,
+            deadline: None,
+            shutdown_button,
+            shutdown_receiver,
+        }
+    }
+
+    pub fn reset(&mut self) -> Result<(), ExecutionError> {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.reset();
+        }
+
+        self.halted = false;
+        self.reset_budget();
+
+        self.memory.load(&self.program)
+    }
+
+    pub fn provide_input(&mut self, value: ValueType) {
+        self.inputs.push_back(value);
+    }
+
+    pub fn step(&mut self) -> Result<State, ExecutionError> {
+        self.traced(Self::advance)
+    }
+
+    // Every error leaving the computer passes through here, so tracers see
+    // each fault exactly once
+    fn traced<T>(
+        &mut self,
+        run: impl FnOnce(&mut Self) -> Result<T, ExecutionError>,
+    ) -> Result<T, ExecutionError> {
+        let result = run(self);
+
+        if let Err(error) = &result {
+            self.fault(error);
+        }
+
+        result
+    }
+
+    fn fault(&mut self, error: &ExecutionError) {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.fault(error);
+        }
+    }
+
+    fn advance(&mut self) -> Result<State, ExecutionError> {
+        self.check_budget()?;
+
+        let state = self.execute_instruction()?;
+
+        match state {
+            State::Halted => {
+                self.halted = true;
+                self.executed += 1;
+            }
+            State::NeedsInput => (),
+            State::Running | State::Output(_) => self.executed += 1,
+        }
+
+        Ok(state)
+    }
+
+    // The budget and the time limit cover everything executed since the last
+    // reset, however the computer is driven, so that polling a program for
+    // output over and over cannot keep it running forever
+    pub fn reset_budget(&mut self) {
+        self.executed = 0;
+        self.deadline = None;
+    }
+
+    fn check_budget(&mut self) -> Result<(), ExecutionError> {
+        if self.deadline.is_none() {
+            self.deadline = self
+                .time_limit
+                .map(|time_limit| Instant::now() + time_limit);
+        }
+
+        let out_of_instructions = self.budget.is_some_and(|budget| self.executed >= budget);
+        let out_of_time = self.executed.is_multiple_of(DEADLINE_CHECK_INTERVAL)
+            && self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline);
+
+        if out_of_instructions || out_of_time {
+            Err(ExecutionError::BudgetExhausted {
+                instructions: self.executed,
+                registers: self.memory.registers(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn execute_instruction(&mut self) -> Result<State, ExecutionError> {
+        let instruction = self.memory.decode(&self.instruction_set)?;
+
+        match &mut self.tracer {
+            None => instruction.execute(&self.instruction_set, &mut self.memory, &mut self.inputs),
+            Some(tracer) => {
+                let registers = self.memory.registers();
+                let opcode = self.memory.read(registers.ip as ValueType)?;
+                let parameters = self.memory.parameters(instruction.modes());
+                let operands = instruction.operands(&self.memory);
+
+                let result =
+                    instruction.execute(&self.instruction_set, &mut self.memory, &mut self.inputs);
+
+                if let (Ok(state), Ok(parameters), Ok(operands)) = (&result, parameters, operands) {
+                    if *state != State::NeedsInput {
+                        tracer.trace(&Trace {
+                            ip: registers.ip,
+                            relative_base: registers.relative_base,
+                            opcode,
+                            parameters: parameters[..instruction.modes().len()]
+                                .iter()
+                                .map(|parameter| parameter.value)
+                                .collect(),
+                            operands,
+                            write: self.memory.last_write,
+                            builtin: instruction.opcode(),
+                        });
+                    }
+                }
+
+                result
+            }
+        }
     }
 
-    pub fn attach_io(&mut self, io: Box<dyn Io>) {
-        self.io = io;
+    pub fn run_until_output(&mut self) -> Result<State, ExecutionError> {
+        self.traced(|computer| loop {
+            match computer.advance()? {
+                State::Running => (),
+                state => return Ok(state),
+            }
+        })
     }
 
-    pub fn get_io(&mut self) -> (mpsc::Sender<ValueType>, mpsc::Receiver<ValueType>) {
-        let (io, sender, receiver) = AsyncIo::new();
-
-        self.io = Box::new(io);
+    pub fn run_until_input_needed(&mut self) -> Result<(State, Vec<ValueType>), ExecutionError> {
+        self.traced(|computer| {
+            let mut outputs = vec![];
 
-        (sender, receiver)
+            loop {
+                match computer.advance()? {
+                    State::Running => (),
+                    State::Output(value) => outputs.push(value),
+                    state => return Ok((state, outputs)),
+                }
+            }
+        })
     }
 
-    pub fn dma(&mut self, position: usize) -> &mut ValueType {
-        &mut self.memory.values[position]
-    }
+    pub fn run(&mut self) -> Result<(), ExecutionError> {
+        self.traced(|computer| {
+            computer.reset()?;
 
-    pub fn shutdown_button(&self) -> mpsc::Sender<()> {
-        self.shutdown_button.clone()
+            computer.execute()
+        })
     }
 
-    pub fn run(&mut self) {
-        self.memory.load(&self.program);
-
-        self.execute();
+    pub fn resume(&mut self) -> Result<(), ExecutionError> {
+        self.traced(Self::execute)
     }
 
-    pub fn run_with_values(&mut self, index: usize, values: &[ValueType]) {
-        self.memory.load(&self.program);
-        self.memory.load_values(index, values);
+    pub fn run_with_values(
+        &mut self,
+        index: usize,
+        values: &[ValueType],
+    ) -> Result<(), ExecutionError> {
+        self.traced(|computer| {
+            computer.reset()?;
+            computer.memory.load_values(index, values)?;
 
-        self.execute();
+            computer.execute()
+        })
     }
 
-    fn execute(&mut self) {
+    fn execute(&mut self) -> Result<(), ExecutionError> {
         loop {
             match self.shutdown_receiver.try_recv() {
                 Ok(_) | Err(TryRecvError::Disconnected) => break,
                 Err(TryRecvError::Empty) => (),
             }
 
-            let opcode = self.memory.advance(1)[0];
-            let operation = Opcode::parse(opcode);
-
-            if !operation.execute(&mut self.memory, &mut *self.io) {
-                break;
+            // Errors point at the instruction that raised them, not past it
+            let registers = self.memory.registers();
+
+            match self.advance()? {
+                State::Running => (),
+                State::Halted => break,
+                State::NeedsInput => {
+                    let value = self
+                        .io
+                        .receive()
+                        .ok_or(ExecutionError::InputClosed { registers })?;
+
+                    if let Some(tracer) = &mut self.tracer {
+                        tracer.receive(value);
+                    }
+
+                    self.inputs.push_back(value);
+                }
+                State::Output(value) => {
+                    if let Some(tracer) = &mut self.tracer {
+                        tracer.send(value);
+                    }
+
+                    self.io
+                        .send(value)
+                        .map_err(|_| ExecutionError::OutputClosed { value, registers })?;
+                }
             }
         }
+
+        Ok(())
     }
 }
 
@@ -414,11 +1541,97 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_program_with_whitespace_and_comments() {
+        let source = "# adds two numbers\n\
+                      1101, 2, 3, 7,   # add\n\
+                      4, 7,\n\
+                      99\n";
+
+        assert_eq!(
+            Ok(Program::new(&[1101, 2, 3, 7, 4, 7, 99])),
+            source.parse::<Program>()
+        );
+    }
+
+    #[test]
+    fn program_parse_errors() {
+        assert_eq!(
+            Err(ProgramParseError {
+                index: 2,
+                token: "x9".to_string()
+            }),
+            "1,2,\nx9,99".parse::<Program>()
+        );
+        assert_eq!(
+            Err(ProgramParseError {
+                index: 0,
+                token: String::new()
+            }),
+            " # nothing here\n".parse::<Program>()
+        );
+        assert_eq!(
+            Err(ProgramParseError {
+                index: 1,
+                token: String::new()
+            }),
+            "1,,2".parse::<Program>()
+        );
+        assert_eq!(
+            Err(ProgramParseError {
+                index: 2,
+                token: String::new()
+            }),
+            "1,2,,\n".parse::<Program>()
+        );
+        assert_eq!(
+            Ok(Program::new(&[1, 2])),
+            "  1 , 2 ,  \n".parse::<Program>()
+        );
+    }
+
+    #[test]
+    fn binary_programs() {
+        let program = Program::new(&[109, -1, 204, 1, 99]);
+        let bytes = program.to_bytes();
+
+        assert_eq!(40, bytes.len());
+        assert_eq!(&[0xff; 8], &bytes[8..16]);
+        assert_eq!(Ok(program), Program::from_bytes(&bytes));
+        assert_eq!(
+            Err(ProgramParseError {
+                index: 1,
+                token: "[ff, ff]".to_string()
+            }),
+            Program::from_bytes(&bytes[..10])
+        );
+    }
+
+    #[test]
+    fn load_from_files() {
+        let directory = std::env::temp_dir();
+        let text = directory.join(format!("intcode-program-{}.txt", std::process::id()));
+        let binary = directory.join(format!("intcode-program-{}.bin", std::process::id()));
+
+        fs::write(&text, "104,7,\n99\n").unwrap();
+        fs::write(&binary, Program::new(&[104, 8, 99]).to_bytes()).unwrap();
+
+        let mut computer = Computer::from_path(&text).unwrap();
+        let program = Program::from_binary_file(&binary).unwrap();
+
+        fs::remove_file(&text).unwrap();
+        fs::remove_file(&binary).unwrap();
+
+        assert_eq!(Ok(State::Output(7)), computer.run_until_output());
+        assert_eq!(&[104, 8, 99], program.values());
+        assert!(matches!(Computer::from_path(&text), Err(LoadError::Io(_))));
+    }
+
     #[test]
     fn addition() {
         let mut computer = Computer::new("1,0,0,0,99").unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(vec![2, 0, 0, 0, 99], &computer.memory.values[..5]);
     }
@@ -427,7 +1640,7 @@ mod tests {
     fn multiplication() {
         let mut computer = Computer::new("2,3,0,3,99").unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(vec![2, 3, 0, 6, 99], &computer.memory.values[..5]);
     }
@@ -436,7 +1649,7 @@ mod tests {
     fn large_multiplication() {
         let mut computer = Computer::new("2,4,4,5,99,0").unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(vec![2, 4, 4, 5, 99, 9801], &computer.memory.values[..6]);
     }
@@ -445,7 +1658,7 @@ mod tests {
     fn multiple_operations() {
         let mut computer = Computer::new("1,1,1,4,99,5,6,0,99").unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(
             vec![30, 1, 1, 4, 2, 5, 6, 0, 99],
@@ -461,7 +1674,7 @@ mod tests {
 
         sender.send(1).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(1, receiver.recv().unwrap());
     }
@@ -470,7 +1683,7 @@ mod tests {
     fn parameter_modes() {
         let mut computer = Computer::new("1002,4,3,4,33").unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(vec![1002, 4, 3, 4, 99], &computer.memory.values[..5]);
     }
@@ -479,7 +1692,7 @@ mod tests {
     fn negative_value() {
         let mut computer = Computer::new("1101,100,-1,4,0").unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(vec![1101, 100, -1, 4, 99], &computer.memory.values[..5]);
     }
@@ -492,13 +1705,13 @@ mod tests {
 
         sender.send(8).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(1, receiver.recv().unwrap());
 
         sender.send(10).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(0, receiver.recv().unwrap());
     }
@@ -511,13 +1724,13 @@ mod tests {
 
         sender.send(6).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(1, receiver.recv().unwrap());
 
         sender.send(10).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(0, receiver.recv().unwrap());
     }
@@ -530,13 +1743,13 @@ mod tests {
 
         sender.send(8).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(1, receiver.recv().unwrap());
 
         sender.send(10).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(0, receiver.recv().unwrap());
     }
@@ -549,13 +1762,13 @@ mod tests {
 
         sender.send(6).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(1, receiver.recv().unwrap());
 
         sender.send(10).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(0, receiver.recv().unwrap());
     }
@@ -568,13 +1781,13 @@ mod tests {
 
         sender.send(0).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(0, receiver.recv().unwrap());
 
         sender.send(12).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(1, receiver.recv().unwrap());
     }
@@ -587,13 +1800,13 @@ mod tests {
 
         sender.send(0).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(0, receiver.recv().unwrap());
 
         sender.send(12).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(1, receiver.recv().unwrap());
     }
@@ -604,25 +1817,25 @@ mod tests {
                        1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,\
                        999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99";
 
-        let mut computer = Computer::new(&program).unwrap();
+        let mut computer = Computer::new(program).unwrap();
 
         let (sender, receiver) = computer.get_io();
 
         sender.send(6).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(999, receiver.recv().unwrap());
 
         sender.send(8).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(1000, receiver.recv().unwrap());
 
         sender.send(12).unwrap();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(1001, receiver.recv().unwrap());
     }
@@ -632,11 +1845,11 @@ mod tests {
         let program = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
         let values = program.parse::<Program>().unwrap().values;
 
-        let mut computer = Computer::new(&program).unwrap();
+        let mut computer = Computer::new(program).unwrap();
 
         let (_, receiver) = computer.get_io();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(
             values,
@@ -644,13 +1857,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn arithmetic_overflow() {
+        let program = "1101,9223372036854775807,1,9,4,9,109,-1,99,0";
+        let mut computer = Computer::new(program).unwrap();
+
+        assert_eq!(
+            Err(ExecutionError::Overflow {
+                registers: Registers {
+                    ip: 0,
+                    relative_base: 0
+                }
+            }),
+            computer.run_until_output()
+        );
+
+        let mut computer = Computer::new(program)
+            .unwrap()
+            .arithmetic(Arithmetic::Wrapping);
+
+        assert_eq!(
+            Ok(State::Output(ValueType::MIN)),
+            computer.run_until_output()
+        );
+
+        let mut computer = Computer::new("109,-9223372036854775807,109,-2,99").unwrap();
+
+        assert_eq!(
+            Err(ExecutionError::Overflow {
+                registers: Registers {
+                    ip: 2,
+                    relative_base: -9_223_372_036_854_775_807
+                }
+            }),
+            computer.run_until_output()
+        );
+    }
+
     #[test]
     fn multiply_big_integers() {
         let mut computer = Computer::new("1102,34915192,34915192,7,4,7,99,0").unwrap();
 
         let (_, receiver) = computer.get_io();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(1_219_070_632_396_864, receiver.recv().unwrap());
     }
@@ -661,8 +1911,427 @@ mod tests {
 
         let (_, receiver) = computer.get_io();
 
-        computer.run();
+        computer.run().unwrap();
 
         assert_eq!(1_125_899_906_842_624, receiver.recv().unwrap());
     }
+
+    #[test]
+    fn invalid_opcode() {
+        let mut computer = Computer::new("1101,1,1,5,98,0").unwrap();
+
+        assert_eq!(
+            Err(ExecutionError::InvalidOpcode {
+                opcode: 98,
+                registers: Registers {
+                    ip: 4,
+                    relative_base: 0
+                },
+            }),
+            computer.run()
+        );
+    }
+
+    #[test]
+    fn invalid_parameter_mode() {
+        let mut computer = Computer::new("109,3,3001,0,0,0,99").unwrap();
+
+        assert_eq!(
+            Err(ExecutionError::InvalidParameterMode {
+                opcode: 3001,
+                registers: Registers {
+                    ip: 2,
+                    relative_base: 3
+                },
+            }),
+            computer.run()
+        );
+    }
+
+    #[test]
+    fn negative_address() {
+        let mut computer = Computer::new("109,-5,22201,1,1,1,99").unwrap();
+
+        assert_eq!(
+            Err(ExecutionError::NegativeAddress {
+                address: -4,
+                registers: Registers {
+                    ip: 2,
+                    relative_base: -5
+                },
+            }),
+            computer.run()
+        );
+    }
+
+    #[test]
+    fn jump_beyond_memory_limit() {
+        let mut computer = Computer::new("1105,1,100000").unwrap().memory_limit(65_536);
+
+        assert_eq!(
+            Err(ExecutionError::MemoryLimitExceeded {
+                address: 100_000,
+                limit: 65_536,
+                registers: Registers {
+                    ip: 100_000,
+                    relative_base: 0
+                },
+            }),
+            computer.run()
+        );
+    }
+
+    #[test]
+    fn high_address() {
+        let mut computer = Computer::new("1101,1,2,1000000,4,1000000,99").unwrap();
+
+        let (_, receiver) = computer.get_io();
+
+        computer.run().unwrap();
+
+        assert_eq!(3, receiver.recv().unwrap());
+        assert_eq!(Ok(&mut 3), computer.dma(1_000_000));
+        assert_eq!(7, computer.memory.decoded.len());
+        assert!(matches!(
+            computer.dma(1 << 40),
+            Err(ExecutionError::MemoryLimitExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn values_beyond_memory_limit() {
+        let mut computer = Computer::new("99").unwrap();
+
+        assert!(matches!(
+            computer.run_with_values(usize::MAX, &[1, 2]),
+            Err(ExecutionError::MemoryLimitExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn program_larger_than_memory_size() {
+        let mut values = vec![0; 10_000];
+        values[..4].copy_from_slice(&[1106, 0, 9_997, 0]);
+        values[9_997..].copy_from_slice(&[104, 42, 99]);
+
+        let program = values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let mut computer = Computer::new(&program).unwrap().memory_size(16);
+
+        let (_, receiver) = computer.get_io();
+
+        computer.run().unwrap();
+
+        assert_eq!(42, receiver.recv().unwrap());
+    }
+
+    #[test]
+    fn program_larger_than_memory_limit() {
+        let mut computer = Computer::new("1,0,0,0,99").unwrap().memory_limit(4);
+
+        assert_eq!(
+            Err(ExecutionError::MemoryLimitExceeded {
+                address: 4,
+                limit: 4,
+                registers: Registers::default(),
+            }),
+            computer.run()
+        );
+    }
+
+    #[test]
+    fn immediate_write() {
+        let mut computer = Computer::new("11101,1,1,0,99").unwrap();
+
+        assert_eq!(
+            Err(ExecutionError::ImmediateWrite {
+                registers: Registers {
+                    ip: 0,
+                    relative_base: 0
+                },
+            }),
+            computer.run()
+        );
+    }
+
+    #[test]
+    fn input_closed() {
+        let mut computer = Computer::new("104,1,3,0,99").unwrap();
+
+        let (sender, receiver) = computer.get_io();
+
+        drop(sender);
+
+        assert_eq!(
+            Err(ExecutionError::InputClosed {
+                registers: Registers {
+                    ip: 2,
+                    relative_base: 0
+                },
+            }),
+            computer.run()
+        );
+        assert_eq!(1, receiver.recv().unwrap());
+    }
+
+    #[test]
+    fn output_closed() {
+        let mut computer = Computer::new("104,1,104,2,99").unwrap();
+
+        let (_, receiver) = computer.get_io();
+
+        drop(receiver);
+
+        assert_eq!(
+            Err(ExecutionError::OutputClosed {
+                value: 1,
+                registers: Registers {
+                    ip: 0,
+                    relative_base: 0
+                },
+            }),
+            computer.run()
+        );
+    }
+
+    #[test]
+    fn devices_and_legacy_io() {
+        struct Counter(ValueType);
+
+        impl Io for Counter {
+            fn receive(&mut self) -> ValueType {
+                self.0 += 1;
+                self.0
+            }
+        }
+
+        struct Limited(Vec<ValueType>);
+
+        impl Device for Limited {
+            fn send(&mut self, value: ValueType) -> Result<(), Closed> {
+                self.0.push(value);
+
+                Ok(())
+            }
+
+            fn receive(&mut self) -> Option<ValueType> {
+                self.0.pop()
+            }
+        }
+
+        let mut computer = Computer::new("3,9,3,10,1,9,10,11,99,0,0,0").unwrap();
+
+        computer.attach_io(Box::new(Counter(0)));
+        computer.run().unwrap();
+
+        assert_eq!(3, computer.memory()[11]);
+
+        let device = Arc::new(Mutex::new(Limited(vec![4])));
+        let mut computer = Computer::new("3,0,4,0,3,0,3,0,99").unwrap();
+
+        computer.attach_device(Box::new(device.clone()));
+
+        assert_eq!(
+            Err(ExecutionError::InputClosed {
+                registers: Registers {
+                    ip: 6,
+                    relative_base: 0
+                },
+            }),
+            computer.run()
+        );
+        assert!(device.lock().unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn step_through_program() {
+        let mut computer = Computer::new("3,0,1001,0,5,0,4,0,99").unwrap();
+
+        assert_eq!(Ok(State::NeedsInput), computer.step());
+        assert_eq!(Ok(State::NeedsInput), computer.step());
+        assert_eq!(0, computer.registers().ip);
+
+        computer.provide_input(10);
+
+        assert_eq!(Ok(State::Running), computer.step());
+        assert_eq!(Ok(State::Running), computer.step());
+        assert_eq!(Ok(State::Output(15)), computer.step());
+        assert_eq!(Ok(State::Halted), computer.step());
+        assert_eq!(Ok(State::Halted), computer.step());
+        assert_eq!(8, computer.registers().ip);
+    }
+
+    #[test]
+    fn run_until_output() {
+        let program = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let values = program.parse::<Program>().unwrap().values;
+
+        let mut computer = Computer::new(program).unwrap();
+
+        for &value in &values {
+            assert_eq!(Ok(State::Output(value)), computer.run_until_output());
+        }
+
+        assert_eq!(Ok(State::Halted), computer.run_until_output());
+        assert_eq!(16, computer.registers().relative_base);
+    }
+
+    #[test]
+    fn run_until_input_needed() {
+        let mut computer = Computer::new("104,1,104,2,3,0,4,0,99").unwrap();
+
+        assert_eq!(
+            Ok((State::NeedsInput, vec![1, 2])),
+            computer.run_until_input_needed()
+        );
+
+        computer.provide_input(7);
+
+        assert_eq!(
+            Ok((State::Halted, vec![7])),
+            computer.run_until_input_needed()
+        );
+
+        computer.reset().unwrap();
+        computer.provide_input(8);
+
+        assert_eq!(
+            Ok((State::Halted, vec![1, 2, 8])),
+            computer.run_until_input_needed()
+        );
+    }
+
+    #[test]
+    fn fork() {
+        let mut computer = Computer::new("3,11,3,12,2,11,12,13,4,13,99,0,0,0").unwrap();
+
+        computer.provide_input(6);
+
+        assert_eq!(
+            Ok((State::NeedsInput, vec![])),
+            computer.run_until_input_needed()
+        );
+
+        let mut forks = (1..=3)
+            .map(|_| computer.fork(Box::new(NullIo)))
+            .collect::<Vec<_>>();
+
+        for (fork, value) in forks.iter_mut().zip(1..) {
+            fork.provide_input(value);
+
+            assert_eq!(
+                Ok((State::Halted, vec![6 * value])),
+                fork.run_until_input_needed()
+            );
+        }
+
+        assert_eq!(2, computer.registers().ip);
+        assert!(!computer.halted());
+    }
+
+    #[test]
+    fn fork_has_its_own_shutdown_button() {
+        let mut computer = Computer::new("3,11,3,12,2,11,12,13,4,13,99,0,0,0").unwrap();
+
+        computer.provide_input(6);
+        computer.run_until_input_needed().unwrap();
+
+        let mut fork = computer.fork(Box::new(NullIo));
+        let (sender, receiver) = fork.get_io();
+
+        computer.shutdown_button().send(()).unwrap();
+        sender.send(7).unwrap();
+
+        assert_eq!(Ok(()), fork.resume());
+        assert_eq!(Ok(42), receiver.try_recv());
+        assert_eq!(Ok(()), computer.resume());
+        assert_eq!(2, computer.registers().ip);
+    }
+
+    #[test]
+    fn self_modifying_code() {
+        let mut computer = Computer::new("104,1,1101,98,1,0,1105,1,0").unwrap();
+
+        assert_eq!(Ok(State::Output(1)), computer.run_until_output());
+        assert_eq!(Ok(State::Halted), computer.run_until_output());
+
+        *computer.dma(0).unwrap() = 104;
+
+        assert_eq!(Ok(State::Output(1)), computer.run_until_output());
+    }
+
+    #[test]
+    fn without_decode_cache() {
+        let mut computer = Computer::new("104,1,1101,98,1,0,1105,1,0")
+            .unwrap()
+            .decode_cache(false);
+
+        assert_eq!(Ok(State::Output(1)), computer.run_until_output());
+        assert_eq!(Ok(State::Halted), computer.run_until_output());
+        assert!(computer.memory.decoded.is_empty());
+    }
+
+    #[test]
+    fn instruction_budget() {
+        let mut computer = Computer::new("104,1,1105,1,0")
+            .unwrap()
+            .instruction_budget(5);
+
+        assert_eq!(
+            Err(ExecutionError::BudgetExhausted {
+                instructions: 5,
+                registers: Registers {
+                    ip: 2,
+                    relative_base: 0
+                }
+            }),
+            computer.run()
+        );
+        assert!(matches!(
+            computer.run_until_output(),
+            Err(ExecutionError::BudgetExhausted {
+                instructions: 5,
+                ..
+            })
+        ));
+
+        computer.reset_budget();
+
+        assert_eq!(Ok(State::Output(1)), computer.run_until_output());
+    }
+
+    #[test]
+    fn budget_applies_to_steps() {
+        let mut computer = Computer::new("1105,1,0").unwrap().instruction_budget(3);
+
+        for _ in 0..3 {
+            assert_eq!(Ok(State::Running), computer.step());
+        }
+
+        assert!(matches!(
+            computer.step(),
+            Err(ExecutionError::BudgetExhausted {
+                instructions: 3,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn time_limit() {
+        let mut computer = Computer::new("1105,1,0")
+            .unwrap()
+            .time_limit(Duration::from_millis(10));
+
+        match computer.run() {
+            Err(ExecutionError::BudgetExhausted { instructions, .. }) => {
+                assert!(instructions > 0)
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
 }
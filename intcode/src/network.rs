@@ -0,0 +1,330 @@
+use std::error::Error;
+use std::fmt;
+
+use super::{Computer, ExecutionError, State, ValueType};
+
+const TIME_SLICE: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Link {
+    Node(usize),
+    Output,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Halted,
+    Idle,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NetworkError {
+    pub node: usize,
+    pub error: ExecutionError,
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "node {}: {}", self.node, self.error)
+    }
+}
+
+impl Error for NetworkError {}
+
+enum Routing {
+    Links(Vec<Link>),
+    Packets(usize),
+}
+
+struct Node {
+    computer: Computer,
+    routing: Routing,
+    packet: Vec<ValueType>,
+    pending: usize,
+}
+
+#[derive(Default)]
+pub struct Network {
+    nodes: Vec<Node>,
+    idle_input: Option<ValueType>,
+    output: Vec<ValueType>,
+    packets: Vec<(ValueType, Vec<ValueType>)>,
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn chain(computers: Vec<Computer>) -> Self {
+        let mut network = Self::new();
+        let count = computers.len();
+
+        for computer in computers {
+            let node = network.add(computer);
+
+            network.connect(
+                node,
+                if node + 1 < count {
+                    Link::Node(node + 1)
+                } else {
+                    Link::Output
+                },
+            );
+        }
+
+        network
+    }
+
+    pub fn ring(computers: Vec<Computer>) -> Self {
+        let mut network = Self::chain(computers);
+
+        if let Some(last) = network.nodes.len().checked_sub(1) {
+            network.connect(last, Link::Node(0));
+        }
+
+        network
+    }
+
+    pub fn idle_input(mut self, value: ValueType) -> Self {
+        self.idle_input = Some(value);
+
+        self
+    }
+
+    pub fn add(&mut self, computer: Computer) -> usize {
+        self.nodes.push(Node {
+            computer,
+            routing: Routing::Links(vec![]),
+            packet: vec![],
+            pending: 0,
+        });
+
+        self.nodes.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node(&mut self, node: usize) -> &mut Computer {
+        &mut self.nodes[node].computer
+    }
+
+    pub fn connect(&mut self, from: usize, to: Link) {
+        match &mut self.nodes[from].routing {
+            Routing::Links(links) => links.push(to),
+            routing => *routing = Routing::Links(vec![to]),
+        }
+    }
+
+    pub fn broadcast(&mut self, from: usize) {
+        for to in (0..self.nodes.len()).filter(|&to| to != from) {
+            self.connect(from, Link::Node(to));
+        }
+    }
+
+    // Outputs are grouped into packets of the given size, the first value
+    // being the destination node; packets to unknown addresses are kept
+    pub fn route_packets(&mut self, node: usize, size: usize) {
+        self.nodes[node].routing = Routing::Packets(size);
+    }
+
+    pub fn send(&mut self, node: usize, value: ValueType) {
+        self.nodes[node].computer.provide_input(value);
+        self.nodes[node].pending += 1;
+    }
+
+    pub fn take_output(&mut self) -> Vec<ValueType> {
+        self.output.split_off(0)
+    }
+
+    pub fn take_packets(&mut self) -> Vec<(ValueType, Vec<ValueType>)> {
+        self.packets.split_off(0)
+    }
+
+    // Nodes take turns of at most TIME_SLICE instructions. A round in which
+    // no node had pending input, produced output or used up its slice means
+    // every running node is blocked on input, so the network is idle
+    pub fn run(&mut self) -> Result<Outcome, NetworkError> {
+        loop {
+            let mut busy = false;
+
+            for node in 0..self.nodes.len() {
+                busy |= self
+                    .turn(node)
+                    .map_err(|error| NetworkError { node, error })?;
+            }
+
+            if !busy {
+                break;
+            }
+        }
+
+        if self.nodes.iter().all(|node| node.computer.halted()) {
+            Ok(Outcome::Halted)
+        } else {
+            Ok(Outcome::Idle)
+        }
+    }
+
+    fn turn(&mut self, node: usize) -> Result<bool, ExecutionError> {
+        let mut busy = self.nodes[node].pending > 0;
+        let mut idle_input = self.idle_input;
+
+        self.nodes[node].pending = 0;
+
+        for _ in 0..TIME_SLICE {
+            if self.nodes[node].computer.halted() {
+                return Ok(busy);
+            }
+
+            match self.nodes[node].computer.step()? {
+                State::Running | State::Halted => (),
+                State::NeedsInput => match idle_input.take() {
+                    Some(value) => self.nodes[node].computer.provide_input(value),
+                    None => return Ok(busy),
+                },
+                State::Output(value) => {
+                    self.deliver(node, value);
+                    busy = true;
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn deliver(&mut self, from: usize, value: ValueType) {
+        let node = &mut self.nodes[from];
+
+        match &node.routing {
+            Routing::Links(links) => {
+                for link in links.clone() {
+                    match link {
+                        Link::Node(to) => self.send(to, value),
+                        Link::Output => self.output.push(value),
+                    }
+                }
+            }
+            &Routing::Packets(size) => {
+                node.packet.push(value);
+
+                if node.packet.len() == size {
+                    let packet = node.packet.split_off(0);
+                    let address = packet[0];
+
+                    match self.nodes.get(address as usize) {
+                        Some(_) if address >= 0 => {
+                            for &value in &packet[1..] {
+                                self.send(address as usize, value);
+                            }
+                        }
+                        _ => self.packets.push((address, packet[1..].to_vec())),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADD_ONE: &str = "3,9,1001,9,1,9,4,9,99,0";
+
+    fn computers(program: &str, count: usize) -> Vec<Computer> {
+        (0..count)
+            .map(|_| Computer::new(program).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn chain() {
+        let mut network = Network::chain(computers(ADD_ONE, 5));
+
+        network.send(0, 10);
+
+        assert_eq!(Ok(Outcome::Halted), network.run());
+        assert_eq!(vec![15], network.take_output());
+    }
+
+    #[test]
+    fn ring() {
+        let program = "3,20,1001,20,1,20,4,20,1001,21,-1,21,1005,21,0,99,0,0,0,0,0,3";
+        let mut network = Network::ring(computers(program, 3));
+
+        network.send(0, 0);
+
+        assert_eq!(Ok(Outcome::Halted), network.run());
+        assert_eq!(vec![3, 6, 9], network.take_output());
+    }
+
+    #[test]
+    fn broadcast_and_idle() {
+        let mut network = Network::new();
+        let source = network.add(Computer::new("104,7,104,8,99").unwrap());
+
+        for _ in 0..3 {
+            let node = network.add(Computer::new("3,100,4,100,1105,1,0").unwrap());
+            network.connect(node, Link::Output);
+        }
+
+        network.broadcast(source);
+
+        assert_eq!(Ok(Outcome::Idle), network.run());
+        assert_eq!(vec![7, 8, 7, 8, 7, 8], network.take_output());
+    }
+
+    #[test]
+    fn packets() {
+        let mut network = Network::new().idle_input(-1);
+        let router = "3,100,1008,100,-1,101,1005,101,0,\
+                      1001,100,1,102,4,102,4,102,1105,1,0";
+
+        for _ in 0..2 {
+            let node = network.add(Computer::new(router).unwrap());
+            network.route_packets(node, 2);
+        }
+
+        network.send(0, 0);
+
+        assert_eq!(Ok(Outcome::Idle), network.run());
+        assert_eq!(vec![(2, vec![2])], network.take_packets());
+    }
+
+    #[test]
+    fn errors() {
+        let mut network = Network::chain(vec![
+            Computer::new(ADD_ONE).unwrap(),
+            Computer::new("3,0,98").unwrap(),
+        ]);
+
+        network.send(0, 1);
+
+        assert_eq!(Some(1), network.run().err().map(|error| error.node));
+    }
+
+    #[test]
+    fn budgets() {
+        let mut network = Network::new();
+
+        network.add(Computer::new("1105,1,0").unwrap().instruction_budget(100));
+
+        assert!(matches!(
+            network.run(),
+            Err(NetworkError {
+                node: 0,
+                error: ExecutionError::BudgetExhausted {
+                    instructions: 100,
+                    ..
+                },
+            })
+        ));
+    }
+}
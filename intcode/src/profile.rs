@@ -0,0 +1,279 @@
+use std::fmt::Write;
+
+use super::trace::{Trace, Tracer};
+use super::{Opcode, ValueType};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    pub end: usize,
+    pub instructions: usize,
+    pub hits: u64,
+}
+
+impl Block {
+    pub fn executed(&self) -> u64 {
+        self.hits * self.instructions as u64
+    }
+}
+
+#[derive(Default)]
+pub struct Profiler {
+    hits: Vec<u64>,
+    lengths: Vec<usize>,
+    leaders: Vec<bool>,
+    terminators: Vec<bool>,
+    fallthrough: Option<usize>,
+    runs: u64,
+    instructions: u64,
+    receives: u64,
+    sends: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn receives(&self) -> u64 {
+        self.receives
+    }
+
+    pub fn sends(&self) -> u64 {
+        self.sends
+    }
+
+    pub fn hits(&self, address: usize) -> u64 {
+        self.hits.get(address).cloned().unwrap_or(0)
+    }
+
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![];
+
+        for start in (0..self.hits.len()).filter(|&address| self.leaders[address]) {
+            let mut address = start;
+            let mut instructions = 0;
+
+            loop {
+                instructions += 1;
+
+                let next = address + self.lengths[address];
+
+                if self.terminators[address]
+                    || self.hits(next) == 0
+                    || self.leaders.get(next) == Some(&true)
+                {
+                    blocks.push(Block {
+                        start,
+                        end: next,
+                        instructions,
+                        hits: self.hits[start],
+                    });
+
+                    break;
+                }
+
+                address = next;
+            }
+        }
+
+        blocks
+    }
+
+    pub fn hottest_blocks(&self, count: usize) -> Vec<Block> {
+        let mut blocks = self.blocks();
+
+        blocks.sort_by(|a, b| b.executed().cmp(&a.executed()).then(a.start.cmp(&b.start)));
+        blocks.truncate(count);
+
+        blocks
+    }
+
+    pub fn report(&self, count: usize) -> String {
+        let mut report = String::new();
+
+        writeln!(report, "runs: {}", self.runs).unwrap();
+        writeln!(
+            report,
+            "instructions: {} ({} per run)",
+            self.instructions,
+            self.instructions / self.runs.max(1)
+        )
+        .unwrap();
+        writeln!(
+            report,
+            "io: {} receives, {} sends",
+            self.receives, self.sends
+        )
+        .unwrap();
+        writeln!(report, "hottest blocks:").unwrap();
+        writeln!(
+            report,
+            "{:>13} {:>12} {:>12} {:>8}",
+            "addresses", "hits", "executed", "share"
+        )
+        .unwrap();
+
+        for block in self.hottest_blocks(count) {
+            writeln!(
+                report,
+                "{:>13} {:>12} {:>12} {:>7.2}%",
+                format!("{}-{}", block.start, block.end - 1),
+                block.hits,
+                block.executed(),
+                block.executed() as f64 * 100.0 / self.instructions.max(1) as f64
+            )
+            .unwrap();
+        }
+
+        report
+    }
+
+    fn record(&mut self, ip: usize, length: usize, terminator: bool) {
+        if ip >= self.hits.len() {
+            self.hits.resize(ip + 1, 0);
+            self.lengths.resize(ip + 1, 1);
+            self.leaders.resize(ip + 1, false);
+            self.terminators.resize(ip + 1, false);
+        }
+
+        self.hits[ip] += 1;
+        self.lengths[ip] = length;
+        self.terminators[ip] |= terminator;
+
+        if self.fallthrough != Some(ip) {
+            self.leaders[ip] = true;
+        }
+
+        self.fallthrough = if terminator { None } else { Some(ip + length) };
+    }
+}
+
+impl Tracer for Profiler {
+    fn trace(&mut self, trace: &Trace) {
+        let terminator = matches!(
+            trace.builtin,
+            Some(Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Halt)
+        );
+
+        self.instructions += 1;
+        self.record(trace.ip, 1 + trace.parameters.len(), terminator);
+    }
+
+    fn reset(&mut self) {
+        self.runs += 1;
+        self.fallthrough = None;
+    }
+
+    fn receive(&mut self, _value: ValueType) {
+        self.receives += 1;
+    }
+
+    fn send(&mut self, _value: ValueType) {
+        self.sends += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Computer;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn counts() {
+        let profiler = Arc::new(Mutex::new(Profiler::new()));
+        let mut computer = Computer::new("3,0,4,0,99").unwrap();
+
+        computer.attach_tracer(Box::new(profiler.clone()));
+
+        let (sender, receiver) = computer.get_io();
+
+        for value in 1..=3 {
+            sender.send(value).unwrap();
+            computer.run().unwrap();
+
+            assert_eq!(value, receiver.recv().unwrap());
+        }
+
+        let profiler = profiler.lock().unwrap();
+
+        assert_eq!(3, profiler.runs());
+        assert_eq!(9, profiler.instructions());
+        assert_eq!(3, profiler.receives());
+        assert_eq!(3, profiler.sends());
+        assert_eq!(3, profiler.hits(2));
+        assert_eq!(0, profiler.hits(3));
+    }
+
+    #[test]
+    fn hottest_blocks() {
+        let program = "1101,5,0,16,\
+                       1001,16,-1,16,1001,17,1,17,1005,16,4,\
+                       99,0,0";
+
+        let profiler = Arc::new(Mutex::new(Profiler::new()));
+        let mut computer = Computer::new(program).unwrap();
+
+        computer.attach_tracer(Box::new(profiler.clone()));
+        computer.run().unwrap();
+
+        let profiler = profiler.lock().unwrap();
+
+        assert_eq!(
+            vec![
+                Block {
+                    start: 4,
+                    end: 15,
+                    instructions: 3,
+                    hits: 5,
+                },
+                Block {
+                    start: 0,
+                    end: 4,
+                    instructions: 1,
+                    hits: 1,
+                },
+                Block {
+                    start: 15,
+                    end: 16,
+                    instructions: 1,
+                    hits: 1,
+                },
+            ],
+            profiler.hottest_blocks(5)
+        );
+        assert_eq!(17, profiler.instructions());
+    }
+
+    #[test]
+    fn custom_opcodes_do_not_end_blocks() {
+        use crate::isa::InstructionSet;
+        use crate::State;
+
+        let set = InstructionSet::new().define(5, 0, &[], |_| Ok(State::Running));
+        let profiler = Arc::new(Mutex::new(Profiler::new()));
+        let mut computer = Computer::new("5,5,99").unwrap().instruction_set(set);
+
+        computer.attach_tracer(Box::new(profiler.clone()));
+        computer.run().unwrap();
+
+        assert_eq!(
+            vec![Block {
+                start: 0,
+                end: 3,
+                instructions: 3,
+                hits: 1,
+            }],
+            profiler.lock().unwrap().blocks()
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use super::{Computer, ExecutionError, Program, State, ValueType};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Candidate {
+    pub patches: Vec<(usize, Vec<ValueType>)>,
+    pub inputs: Vec<ValueType>,
+}
+
+impl Candidate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn patch(mut self, address: usize, values: &[ValueType]) -> Self {
+        self.patches.push((address, values.to_vec()));
+
+        self
+    }
+
+    pub fn inputs(mut self, inputs: &[ValueType]) -> Self {
+        self.inputs.extend_from_slice(inputs);
+
+        self
+    }
+}
+
+pub struct Run<'a> {
+    pub computer: &'a Computer,
+    pub outputs: Vec<ValueType>,
+    pub result: Result<State, ExecutionError>,
+}
+
+pub struct Search<'a> {
+    program: &'a Program,
+    threads: usize,
+    budget: Option<u64>,
+}
+
+impl<'a> Search<'a> {
+    pub fn new(program: &'a Program) -> Self {
+        Self {
+            program,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            budget: None,
+        }
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+
+        self
+    }
+
+    pub fn instruction_budget(mut self, budget: u64) -> Self {
+        self.budget = Some(budget);
+
+        self
+    }
+
+    // Candidates are handed out in order, and workers stop picking up new
+    // ones past the earliest match, so the first matching candidate wins
+    // regardless of scheduling
+    pub fn find<'c, F>(&self, candidates: &'c [Candidate], predicate: F) -> Option<&'c Candidate>
+    where
+        F: Fn(&Run) -> bool + Sync,
+    {
+        let next = AtomicUsize::new(0);
+        let found = AtomicUsize::new(usize::MAX);
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+
+                    if i >= candidates.len() || i > found.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    if self.run(&candidates[i], &predicate) {
+                        found.fetch_min(i, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+
+        candidates.get(found.into_inner())
+    }
+
+    pub fn maximize<'c, F>(
+        &self,
+        candidates: &'c [Candidate],
+        objective: F,
+    ) -> Option<(&'c Candidate, ValueType)>
+    where
+        F: Fn(&Run) -> Option<ValueType> + Sync,
+    {
+        let next = AtomicUsize::new(0);
+
+        let best = thread::scope(|scope| {
+            let workers = (0..self.threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut best: Option<(ValueType, usize)> = None;
+
+                        loop {
+                            let i = next.fetch_add(1, Ordering::SeqCst);
+
+                            if i >= candidates.len() {
+                                return best;
+                            }
+
+                            if let Some(value) = self.run(&candidates[i], &objective) {
+                                if best.is_none_or(|(best, _)| value > best) {
+                                    best = Some((value, i));
+                                }
+                            }
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            workers
+                .into_iter()
+                .filter_map(|worker| worker.join().unwrap())
+                .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)))
+        });
+
+        best.map(|(value, i)| (&candidates[i], value))
+    }
+
+    fn run<T>(&self, candidate: &Candidate, f: impl Fn(&Run) -> T) -> T {
+        let mut computer = Computer::from_program(self.program);
+
+        if let Some(budget) = self.budget {
+            computer = computer.instruction_budget(budget);
+        }
+
+        computer.inputs.extend(&candidate.inputs);
+
+        let mut outputs = vec![];
+        let result = candidate
+            .patches
+            .iter()
+            .try_for_each(|(address, values)| computer.memory.load_values(*address, values))
+            .and_then(|_| computer.run_until_input_needed())
+            .map(|(state, values)| {
+                outputs = values;
+                state
+            });
+
+        f(&Run {
+            computer: &computer,
+            outputs,
+            result,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_memory_patch() {
+        let program = "1,0,0,0,99,5,7,11,13".parse::<Program>().unwrap();
+        let candidates = (0..9)
+            .flat_map(|noun| (0..9).map(move |verb| Candidate::new().patch(1, &[noun, verb])))
+            .collect::<Vec<_>>();
+
+        let found = Search::new(&program)
+            .threads(4)
+            .find(&candidates, |run| run.computer.memory()[0] == 20);
+
+        assert_eq!(Some(&Candidate::new().patch(1, &[6, 8])), found);
+    }
+
+    #[test]
+    fn find_nothing() {
+        let program = "3,0,4,0,99".parse::<Program>().unwrap();
+        let candidates = (0..10)
+            .map(|input| Candidate::new().inputs(&[input]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            None,
+            Search::new(&program).find(&candidates, |run| run.outputs == [10])
+        );
+    }
+
+    #[test]
+    fn maximize_over_inputs() {
+        let program = "3,20,3,21,2,20,21,22,1002,20,-1,20,1,20,22,22,4,22,99"
+            .parse::<Program>()
+            .unwrap();
+        let candidates = (-5..=5)
+            .flat_map(|a| (-5..=5).map(move |b| Candidate::new().inputs(&[a, b])))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            Some((&Candidate::new().inputs(&[-5, -5]), 30)),
+            Search::new(&program)
+                .threads(3)
+                .maximize(&candidates, |run| run.outputs.first().cloned())
+        );
+    }
+
+    #[test]
+    fn budget() {
+        let program = "3,0,1005,0,2,104,1,99".parse::<Program>().unwrap();
+        let candidates = [Candidate::new().inputs(&[1]), Candidate::new().inputs(&[0])];
+
+        let found = Search::new(&program)
+            .instruction_budget(100)
+            .find(&candidates, |run| run.result.is_ok());
+
+        assert_eq!(Some(&candidates[1]), found);
+    }
+}
@@ -0,0 +1,274 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::{Registers, ValueType};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MachineState {
+    pub memory: Vec<ValueType>,
+    pub registers: Registers,
+    pub halted: bool,
+    pub inputs: Vec<ValueType>,
+}
+
+impl MachineState {
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StateParseError {
+    pub line: usize,
+}
+
+impl fmt::Display for StateParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unable to parse machine state at line {}", self.line)
+    }
+}
+
+impl Error for StateParseError {}
+
+fn join(values: &[ValueType]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// The text format is one "key value" pair per line, in this order:
+//
+//     intcode-state 1
+//     ip 12
+//     relative_base 0
+//     halted 0
+//     inputs 5,7
+//     memory 1101,1,2,9,4,9,104,7,99,3
+//
+// Lists are comma separated and may be empty. Memory beyond the last
+// listed cell is zero.
+impl fmt::Display for MachineState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "intcode-state 1")?;
+        writeln!(f, "ip {}", self.registers.ip)?;
+        writeln!(f, "relative_base {}", self.registers.relative_base)?;
+        writeln!(f, "halted {}", self.halted as u8)?;
+        writeln!(f, "inputs {}", join(&self.inputs))?;
+        writeln!(f, "memory {}", join(&self.memory))
+    }
+}
+
+impl FromStr for MachineState {
+    type Err = StateParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().enumerate();
+
+        let mut field = |key: &str| {
+            let (i, line) = lines.next().ok_or(StateParseError { line: 0 })?;
+            let error = StateParseError { line: i + 1 };
+
+            match line.split_once(' ') {
+                Some((k, value)) if k == key => Ok((value.trim().to_string(), error)),
+                None if line.trim() == key => Ok((String::new(), error)),
+                _ => Err(error),
+            }
+        };
+
+        let number = |(value, error): (String, StateParseError)| value.parse().map_err(|_| error);
+
+        let list = |(value, error): (String, StateParseError)| {
+            if value.is_empty() {
+                Ok(vec![])
+            } else {
+                value
+                    .split(',')
+                    .map(|value| value.trim().parse::<ValueType>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| error)
+            }
+        };
+
+        let version: ValueType = number(field("intcode-state")?)?;
+
+        if version != 1 {
+            return Err(StateParseError { line: 1 });
+        }
+
+        // Unlike the other numbers, ip is an unsigned index into memory
+        let (ip, error) = field("ip")?;
+        let ip = ip.parse::<usize>().map_err(|_| error)?;
+        let relative_base = number(field("relative_base")?)?;
+        let halted: ValueType = number(field("halted")?)?;
+        let inputs = list(field("inputs")?)?;
+        let memory = list(field("memory")?)?;
+
+        Ok(Self {
+            memory,
+            registers: Registers { ip, relative_base },
+            halted: halted != 0,
+            inputs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Computer, ExecutionError, State};
+
+    #[test]
+    fn snapshot_and_restore() {
+        let mut computer = Computer::new("3,11,3,12,1,11,12,13,4,13,99,0,0,0").unwrap();
+
+        computer.provide_input(3);
+
+        assert_eq!(
+            Ok((State::NeedsInput, vec![])),
+            computer.run_until_input_needed()
+        );
+
+        let state = computer.snapshot();
+
+        assert_eq!(
+            MachineState {
+                memory: vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 3],
+                registers: Registers {
+                    ip: 2,
+                    relative_base: 0
+                },
+                halted: false,
+                inputs: vec![],
+            },
+            state
+        );
+
+        computer.provide_input(4);
+
+        assert_eq!(
+            Ok((State::Halted, vec![7])),
+            computer.run_until_input_needed()
+        );
+        assert!(computer.halted());
+
+        computer.restore(&state).unwrap();
+        computer.provide_input(10);
+
+        assert!(!computer.halted());
+        assert_eq!(
+            Ok((State::Halted, vec![13])),
+            computer.run_until_input_needed()
+        );
+    }
+
+    #[test]
+    fn text_format() {
+        let state = MachineState {
+            memory: vec![1101, 1, 2, 9, 4, 9, 104, 7, 99, 3],
+            registers: Registers {
+                ip: 4,
+                relative_base: -2,
+            },
+            halted: false,
+            inputs: vec![5, 7],
+        };
+
+        let text = state.to_string();
+
+        assert_eq!(
+            "intcode-state 1\n\
+             ip 4\n\
+             relative_base -2\n\
+             halted 0\n\
+             inputs 5,7\n\
+             memory 1101,1,2,9,4,9,104,7,99,3\n",
+            text
+        );
+        assert_eq!(Ok(state), text.parse());
+    }
+
+    #[test]
+    fn empty_lists() {
+        let state = "intcode-state 1\nip 0\nrelative_base 0\nhalted 1\ninputs\nmemory 99"
+            .parse::<MachineState>()
+            .unwrap();
+
+        assert!(state.halted);
+        assert!(state.inputs.is_empty());
+        assert_eq!(vec![99], state.memory);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Err(StateParseError { line: 3 }),
+            "intcode-state 1\nip 0\nrelative_base x\nhalted 0\ninputs\nmemory 99"
+                .parse::<MachineState>()
+        );
+        assert_eq!(
+            Err(StateParseError { line: 2 }),
+            "intcode-state 1\nrelative_base 0".parse::<MachineState>()
+        );
+        assert_eq!(
+            Err(StateParseError { line: 2 }),
+            "intcode-state 1\nip -1\nrelative_base 0\nhalted 0\ninputs\nmemory 99"
+                .parse::<MachineState>()
+        );
+    }
+
+    #[test]
+    fn restore_beyond_memory_limit() {
+        let mut computer = Computer::new("99").unwrap().memory_limit(4);
+        let state = MachineState {
+            memory: vec![1, 2, 3, 4, 5],
+            registers: Registers {
+                ip: 0,
+                relative_base: 0,
+            },
+            halted: false,
+            inputs: vec![],
+        };
+
+        assert!(matches!(
+            computer.restore(&state),
+            Err(ExecutionError::MemoryLimitExceeded {
+                address: 4,
+                limit: 4,
+                ..
+            })
+        ));
+        assert_eq!(vec![99], computer.snapshot().memory);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut computer = Computer::new("104,1,104,2,99").unwrap();
+
+        computer.run_until_output().unwrap();
+
+        let path = std::env::temp_dir().join(format!("intcode-state-{}", std::process::id()));
+
+        computer.snapshot().save(&path).unwrap();
+
+        let state = MachineState::load(&path).unwrap();
+
+        fs::remove_file(&path).unwrap();
+
+        let mut restored = Computer::new("99").unwrap();
+        restored.restore(&state).unwrap();
+
+        assert_eq!(Ok(State::Output(2)), restored.run_until_output());
+    }
+}
@@ -0,0 +1,647 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use super::search::{Candidate, Run, Search};
+use super::{Opcode, ParameterMode, Program, State, ValueType, MEMORY_LIMIT};
+
+const INSTRUCTION_LIMIT: usize = 1 << 20;
+
+// Unknown stands for a value read from an address that depends on a variable,
+// and for anything computed from such a value
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Constant(ValueType),
+    Variable(usize),
+    Unknown,
+    Add(Box<Expr>, Box<Expr>),
+    Multiply(Box<Expr>, Box<Expr>),
+    LessThan(Box<Expr>, Box<Expr>),
+    Equals(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn constant(&self) -> Option<ValueType> {
+        match self {
+            Expr::Constant(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn add(a: Expr, b: Expr) -> Option<Expr> {
+        Some(match (a.constant(), b.constant()) {
+            (Some(a), Some(b)) => Expr::Constant(a.checked_add(b)?),
+            (Some(0), None) => b,
+            (None, Some(0)) => a,
+            _ if a == Expr::Unknown || b == Expr::Unknown => Expr::Unknown,
+            _ => Expr::Add(Box::new(a), Box::new(b)),
+        })
+    }
+
+    fn multiply(a: Expr, b: Expr) -> Option<Expr> {
+        Some(match (a.constant(), b.constant()) {
+            (Some(a), Some(b)) => Expr::Constant(a.checked_mul(b)?),
+            (Some(0), None) | (None, Some(0)) => Expr::Constant(0),
+            (Some(1), None) => b,
+            (None, Some(1)) => a,
+            _ if a == Expr::Unknown || b == Expr::Unknown => Expr::Unknown,
+            _ => Expr::Multiply(Box::new(a), Box::new(b)),
+        })
+    }
+
+    fn less_than(a: Expr, b: Expr) -> Expr {
+        match (a.constant(), b.constant()) {
+            (Some(a), Some(b)) => Expr::Constant((a < b) as ValueType),
+            _ if a == Expr::Unknown || b == Expr::Unknown => Expr::Unknown,
+            _ => Expr::LessThan(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn equals(a: Expr, b: Expr) -> Expr {
+        match (a.constant(), b.constant()) {
+            (Some(a), Some(b)) => Expr::Constant((a == b) as ValueType),
+            _ if a == Expr::Unknown || b == Expr::Unknown => Expr::Unknown,
+            _ => Expr::Equals(Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn variables(&self) -> Vec<usize> {
+        let mut variables = match self {
+            Expr::Variable(address) => vec![*address],
+            Expr::Add(a, b) | Expr::Multiply(a, b) | Expr::LessThan(a, b) | Expr::Equals(a, b) => {
+                let mut variables = a.variables();
+                variables.extend(b.variables());
+                variables
+            }
+            _ => vec![],
+        };
+
+        variables.sort_unstable();
+        variables.dedup();
+
+        variables
+    }
+
+    fn is_known(&self) -> bool {
+        match self {
+            Expr::Unknown => false,
+            Expr::Add(a, b) | Expr::Multiply(a, b) | Expr::LessThan(a, b) | Expr::Equals(a, b) => {
+                a.is_known() && b.is_known()
+            }
+            _ => true,
+        }
+    }
+
+    // Overflows evaluate to None, just as they fault the checked computer
+    pub fn evaluate(&self, value: &impl Fn(usize) -> ValueType) -> Option<ValueType> {
+        match self {
+            Expr::Constant(constant) => Some(*constant),
+            Expr::Variable(address) => Some(value(*address)),
+            Expr::Unknown => None,
+            Expr::Add(a, b) => a.evaluate(value)?.checked_add(b.evaluate(value)?),
+            Expr::Multiply(a, b) => a.evaluate(value)?.checked_mul(b.evaluate(value)?),
+            Expr::LessThan(a, b) => Some((a.evaluate(value)? < b.evaluate(value)?) as ValueType),
+            Expr::Equals(a, b) => Some((a.evaluate(value)? == b.evaluate(value)?) as ValueType),
+        }
+    }
+
+    // Coefficients for the given variables plus a constant, if the expression
+    // only adds variables and multiplies them by constants
+    fn linear(&self, variables: &[usize]) -> Option<(Vec<ValueType>, ValueType)> {
+        match self {
+            Expr::Constant(constant) => Some((vec![0; variables.len()], *constant)),
+            Expr::Variable(address) => {
+                let mut coefficients = vec![0; variables.len()];
+                coefficients[variables.iter().position(|variable| variable == address)?] = 1;
+
+                Some((coefficients, 0))
+            }
+            Expr::Add(a, b) => {
+                let (a, a_constant) = a.linear(variables)?;
+                let (b, b_constant) = b.linear(variables)?;
+
+                Some((
+                    a.iter()
+                        .zip(&b)
+                        .map(|(a, b)| a.checked_add(*b))
+                        .collect::<Option<_>>()?,
+                    a_constant.checked_add(b_constant)?,
+                ))
+            }
+            Expr::Multiply(a, b) => {
+                let ((coefficients, constant), factor) = match (a.constant(), b.constant()) {
+                    (Some(factor), _) => (b.linear(variables)?, factor),
+                    (_, Some(factor)) => (a.linear(variables)?, factor),
+                    _ => return None,
+                };
+
+                Some((
+                    coefficients
+                        .iter()
+                        .map(|coefficient| coefficient.checked_mul(factor))
+                        .collect::<Option<_>>()?,
+                    constant.checked_mul(factor)?,
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Constant(value) => write!(f, "{}", value),
+            Expr::Variable(address) => write!(f, "[{}]", address),
+            Expr::Unknown => write!(f, "?"),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Multiply(a, b) => write!(f, "({} * {})", a, b),
+            Expr::LessThan(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Equals(a, b) => write!(f, "({} == {})", a, b),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SymbolicError {
+    SymbolicBranch { ip: usize },
+    SymbolicAddress { ip: usize },
+    SymbolicOpcode { ip: usize },
+    InvalidOpcode { ip: usize, opcode: ValueType },
+    InvalidAddress { ip: usize, address: ValueType },
+    Input { ip: usize },
+    Overflow { ip: usize },
+    InstructionLimit,
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolicError::SymbolicBranch { ip } => {
+                write!(f, "branch depends on a variable at {}", ip)
+            }
+            SymbolicError::SymbolicAddress { ip } => {
+                write!(f, "write address depends on a variable at {}", ip)
+            }
+            SymbolicError::SymbolicOpcode { ip } => {
+                write!(f, "opcode depends on a variable at {}", ip)
+            }
+            SymbolicError::InvalidOpcode { ip, opcode } => {
+                write!(f, "invalid opcode {} at {}", opcode, ip)
+            }
+            SymbolicError::InvalidAddress { ip, address } => {
+                write!(f, "invalid address {} at {}", address, ip)
+            }
+            SymbolicError::Input { ip } => write!(f, "input is not supported at {}", ip),
+            SymbolicError::Overflow { ip } => write!(f, "arithmetic overflow at {}", ip),
+            SymbolicError::InstructionLimit => {
+                write!(f, "no halt within {} instructions", INSTRUCTION_LIMIT)
+            }
+        }
+    }
+}
+
+impl Error for SymbolicError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Execution {
+    pub memory: Vec<Expr>,
+    pub outputs: Vec<Expr>,
+}
+
+impl Execution {
+    pub fn cell(&self, address: usize) -> Expr {
+        self.memory
+            .get(address)
+            .cloned()
+            .unwrap_or(Expr::Constant(0))
+    }
+}
+
+struct Machine {
+    memory: Vec<Expr>,
+    ip: usize,
+    relative_base: ValueType,
+}
+
+impl Machine {
+    fn address(&self, address: ValueType) -> Result<usize, SymbolicError> {
+        if address < 0 || address as usize >= MEMORY_LIMIT {
+            Err(SymbolicError::InvalidAddress {
+                ip: self.ip,
+                address,
+            })
+        } else {
+            Ok(address as usize)
+        }
+    }
+
+    fn cell(&self, address: usize) -> Expr {
+        self.memory
+            .get(address)
+            .cloned()
+            .unwrap_or(Expr::Constant(0))
+    }
+
+    fn target(&self, n: usize, mode: ParameterMode) -> Result<Option<usize>, SymbolicError> {
+        let offset = match mode {
+            ParameterMode::Position => 0,
+            ParameterMode::Relative => self.relative_base,
+            ParameterMode::Immediate => return Ok(None),
+        };
+
+        match self.cell(self.ip + 1 + n).constant() {
+            Some(value) => {
+                let address = value
+                    .checked_add(offset)
+                    .ok_or(SymbolicError::Overflow { ip: self.ip })?;
+
+                self.address(address).map(Some)
+            }
+            None => Err(SymbolicError::SymbolicAddress { ip: self.ip }),
+        }
+    }
+
+    fn get(&self, n: usize, mode: ParameterMode) -> Result<Expr, SymbolicError> {
+        match self.target(n, mode) {
+            Ok(Some(address)) => Ok(self.cell(address)),
+            Ok(None) => Ok(self.cell(self.ip + 1 + n)),
+            Err(SymbolicError::SymbolicAddress { .. }) => Ok(Expr::Unknown),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn set(&mut self, n: usize, mode: ParameterMode, value: Expr) -> Result<(), SymbolicError> {
+        let address = self
+            .target(n, mode)?
+            .ok_or(SymbolicError::SymbolicAddress { ip: self.ip })?;
+
+        if address >= self.memory.len() {
+            self.memory.resize(address + 1, Expr::Constant(0));
+        }
+
+        self.memory[address] = value;
+
+        Ok(())
+    }
+
+    fn known(&self, expr: Expr, error: SymbolicError) -> Result<ValueType, SymbolicError> {
+        expr.constant().ok_or(error)
+    }
+
+    fn run(mut self) -> Result<Execution, SymbolicError> {
+        let mut outputs = vec![];
+
+        for _ in 0..INSTRUCTION_LIMIT {
+            let ip = self.ip;
+            let opcode = self.known(self.cell(ip), SymbolicError::SymbolicOpcode { ip })?;
+            let instruction =
+                Opcode::parse(opcode).map_err(|_| SymbolicError::InvalidOpcode { ip, opcode })?;
+            let operation = instruction.opcode().unwrap();
+            let modes = instruction.modes();
+            let overflow = SymbolicError::Overflow { ip };
+            let next = ip + instruction.len();
+
+            match operation {
+                Opcode::Add => {
+                    let value = Expr::add(self.get(0, modes[0])?, self.get(1, modes[1])?);
+                    self.set(2, modes[2], value.ok_or(overflow)?)?;
+                }
+                Opcode::Multiply => {
+                    let value = Expr::multiply(self.get(0, modes[0])?, self.get(1, modes[1])?);
+                    self.set(2, modes[2], value.ok_or(overflow)?)?;
+                }
+                Opcode::LessThan => {
+                    let value = Expr::less_than(self.get(0, modes[0])?, self.get(1, modes[1])?);
+                    self.set(2, modes[2], value)?;
+                }
+                Opcode::Equals => {
+                    let value = Expr::equals(self.get(0, modes[0])?, self.get(1, modes[1])?);
+                    self.set(2, modes[2], value)?;
+                }
+                Opcode::Input => return Err(SymbolicError::Input { ip }),
+                Opcode::Output => outputs.push(self.get(0, modes[0])?),
+                Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                    let condition = self.get(0, modes[0])?;
+                    let condition = self.known(condition, SymbolicError::SymbolicBranch { ip })?;
+
+                    if (condition != 0) == (operation == Opcode::JumpIfTrue) {
+                        let target = self.get(1, modes[1])?;
+                        let target = self.known(target, SymbolicError::SymbolicBranch { ip })?;
+
+                        self.ip = self.address(target)?;
+
+                        continue;
+                    }
+                }
+                Opcode::AdjustRelativeBase => {
+                    let amount = self.get(0, modes[0])?;
+                    let amount = self.known(amount, SymbolicError::SymbolicAddress { ip })?;
+
+                    self.relative_base = self.relative_base.checked_add(amount).ok_or(overflow)?;
+                }
+                Opcode::Halt => {
+                    return Ok(Execution {
+                        memory: self.memory,
+                        outputs,
+                    })
+                }
+            }
+
+            self.ip = next;
+        }
+
+        Err(SymbolicError::InstructionLimit)
+    }
+}
+
+// Steps through every assignment of the ranges in lexicographic order
+fn advance(values: &mut [ValueType], ranges: &[RangeInclusive<ValueType>]) -> bool {
+    for (value, range) in values.iter_mut().zip(ranges).rev() {
+        if *value < *range.end() {
+            *value += 1;
+
+            return true;
+        }
+
+        *value = *range.start();
+    }
+
+    false
+}
+
+fn assignments(ranges: Vec<RangeInclusive<ValueType>>) -> impl Iterator<Item = Vec<ValueType>> {
+    let mut values = if ranges.iter().any(|range| range.is_empty()) {
+        None
+    } else {
+        Some(
+            ranges
+                .iter()
+                .map(|range| *range.start())
+                .collect::<Vec<_>>(),
+        )
+    };
+
+    std::iter::from_fn(move || {
+        let current = values.clone()?;
+
+        if !advance(values.as_mut()?, &ranges) {
+            values = None;
+        }
+
+        Some(current)
+    })
+}
+
+pub struct Solver<'a> {
+    program: &'a Program,
+    variables: Vec<(usize, RangeInclusive<ValueType>)>,
+    budget: Option<u64>,
+}
+
+impl<'a> Solver<'a> {
+    pub fn new(program: &'a Program) -> Self {
+        Self {
+            program,
+            variables: vec![],
+            budget: None,
+        }
+    }
+
+    pub fn variable(mut self, address: usize, range: RangeInclusive<ValueType>) -> Self {
+        self.variables.push((address, range));
+
+        self
+    }
+
+    pub fn instruction_budget(mut self, budget: u64) -> Self {
+        self.budget = Some(budget);
+
+        self
+    }
+
+    pub fn execute(&self) -> Result<Execution, SymbolicError> {
+        let mut memory = self
+            .program
+            .values()
+            .iter()
+            .map(|&value| Expr::Constant(value))
+            .collect::<Vec<_>>();
+
+        for (address, _) in &self.variables {
+            if *address >= memory.len() {
+                memory.resize(address + 1, Expr::Constant(0));
+            }
+
+            memory[*address] = Expr::Variable(*address);
+        }
+
+        Machine {
+            memory,
+            ip: 0,
+            relative_base: 0,
+        }
+        .run()
+    }
+
+    // Returns the values of the variables, in the order they were added, that
+    // leave the target value at the address when the program halts. The first
+    // solution in lexicographic order wins, whichever way it is found.
+    pub fn solve(&self, address: usize, target: ValueType) -> Option<Vec<ValueType>> {
+        let found = |run: &Run| {
+            run.result == Ok(State::Halted) && run.computer.memory().get(address) == Some(&target)
+        };
+
+        // Unknown cells depend on memory the symbolic run could not follow, so
+        // like symbolic branches they leave searching every assignment
+        let expr = self
+            .execute()
+            .ok()
+            .map(|execution| execution.cell(address))
+            .filter(Expr::is_known);
+
+        match expr {
+            Some(expr) => self.solutions(expr, target).find(|values| {
+                self.search()
+                    .threads(1)
+                    .find(&[self.candidate(values)], found)
+                    .is_some()
+            }),
+            None => {
+                let candidates = assignments(self.ranges())
+                    .map(|values| self.candidate(&values))
+                    .collect::<Vec<_>>();
+
+                self.search().find(&candidates, found).map(|candidate| {
+                    candidate
+                        .patches
+                        .iter()
+                        .map(|(_, values)| values[0])
+                        .collect()
+                })
+            }
+        }
+    }
+
+    // Linear expressions are solved for the last variable; anything else is
+    // evaluated for every assignment. Candidates are verified by running them
+    // because reads from variable addresses are not modelled.
+    fn solutions(&self, expr: Expr, target: ValueType) -> Box<dyn Iterator<Item = Vec<ValueType>>> {
+        let addresses = self
+            .variables
+            .iter()
+            .map(|(address, _)| *address)
+            .collect::<Vec<_>>();
+        let mut ranges = self.ranges();
+
+        match expr.linear(&addresses) {
+            Some((mut coefficients, constant)) if coefficients.last().is_some_and(|&c| c != 0) => {
+                let last = coefficients.pop().unwrap();
+                let range = ranges.pop().unwrap();
+
+                Box::new(assignments(ranges).filter_map(move |mut values| {
+                    let sum = values.iter().zip(&coefficients).try_fold(
+                        constant,
+                        |sum, (value, coefficient)| {
+                            sum.checked_add(value.checked_mul(*coefficient)?)
+                        },
+                    )?;
+                    let remainder = target.checked_sub(sum)?;
+
+                    let value = remainder
+                        .checked_div(last)
+                        .filter(|_| remainder.checked_rem(last) == Some(0))?;
+
+                    if !range.contains(&value) {
+                        return None;
+                    }
+
+                    values.push(value);
+
+                    Some(values)
+                }))
+            }
+            _ => Box::new(assignments(ranges).filter(move |values| {
+                let value = |address| values[addresses.iter().position(|&a| a == address).unwrap()];
+
+                expr.evaluate(&value) == Some(target)
+            })),
+        }
+    }
+
+    fn ranges(&self) -> Vec<RangeInclusive<ValueType>> {
+        self.variables
+            .iter()
+            .map(|(_, range)| range.clone())
+            .collect()
+    }
+
+    fn candidate(&self, values: &[ValueType]) -> Candidate {
+        self.variables
+            .iter()
+            .zip(values)
+            .fold(Candidate::new(), |candidate, ((address, _), &value)| {
+                candidate.patch(*address, &[value])
+            })
+    }
+
+    fn search(&self) -> Search<'a> {
+        Search::new(self.program)
+            .instruction_budget(self.budget.unwrap_or(INSTRUCTION_LIMIT as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOUN_VERB: &str = "1,0,0,3,2,1,17,16,1,16,2,0,99,0,0,0,0,100";
+
+    fn program(text: &str) -> Program {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn expression() {
+        let program = program(NOUN_VERB);
+        let execution = Solver::new(&program)
+            .variable(1, 0..=99)
+            .variable(2, 0..=99)
+            .execute()
+            .unwrap();
+
+        assert_eq!("(([1] * 100) + [2])", execution.cell(0).to_string());
+        assert_eq!(Expr::Unknown, execution.cell(3));
+        assert_eq!(vec![1, 2], execution.cell(0).variables());
+    }
+
+    #[test]
+    fn solve_linear() {
+        let program = program(NOUN_VERB);
+        let solver = Solver::new(&program)
+            .variable(1, 0..=99)
+            .variable(2, 0..=99);
+
+        assert_eq!(Some(vec![12, 34]), solver.solve(0, 1234));
+        assert_eq!(None, solver.solve(0, 10_000));
+    }
+
+    #[test]
+    fn solve_negative_coefficients() {
+        let program = program("1002,5,-1,0,99,0");
+        let solver = Solver::new(&program).variable(5, 0..=1);
+
+        assert_eq!(Some(vec![1]), solver.solve(0, -1));
+        assert_eq!(None, solver.solve(0, ValueType::MIN));
+    }
+
+    #[test]
+    fn solve_nonlinear() {
+        let program = program("2,9,9,10,8,10,11,0,99,0,0,49");
+        let solver = Solver::new(&program).variable(9, -10..=10);
+
+        assert_eq!(
+            "(([9] * [9]) == 49)",
+            solver.execute().unwrap().cell(0).to_string()
+        );
+        assert_eq!(Some(vec![-7]), solver.solve(0, 1));
+        assert_eq!(Some(vec![-10]), solver.solve(0, 0));
+    }
+
+    #[test]
+    fn outputs() {
+        let program = program("1001,7,5,7,4,7,99,0");
+        let execution = Solver::new(&program).variable(7, 0..=9).execute().unwrap();
+
+        assert_eq!(
+            vec![Expr::Add(
+                Box::new(Expr::Variable(7)),
+                Box::new(Expr::Constant(5))
+            )],
+            execution.outputs
+        );
+    }
+
+    #[test]
+    fn falls_back_to_search() {
+        let program = program("1005,13,8,1101,1,1,0,99,1101,2,2,0,99,0");
+        let solver = Solver::new(&program).variable(13, 0..=5);
+
+        assert_eq!(
+            Err(SymbolicError::SymbolicBranch { ip: 0 }),
+            solver.execute()
+        );
+        assert_eq!(Some(vec![0]), solver.solve(0, 2));
+        assert_eq!(Some(vec![1]), solver.solve(0, 4));
+        assert_eq!(None, solver.solve(0, 3));
+    }
+
+    #[test]
+    fn unknown_cells_fall_back_to_search() {
+        let program = program("1,0,0,0,99,5,7,11,13");
+        let solver = Solver::new(&program).variable(1, 0..=8).variable(2, 0..=8);
+
+        assert_eq!(Expr::Unknown, solver.execute().unwrap().cell(0));
+        assert_eq!(Some(vec![6, 8]), solver.solve(0, 20));
+    }
+}
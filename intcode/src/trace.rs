@@ -0,0 +1,451 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+
+use super::{disasm, ExecutionError, MemoryWrite, Opcode, ValueType};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trace {
+    pub ip: usize,
+    pub relative_base: ValueType,
+    pub opcode: ValueType,
+    pub parameters: Vec<ValueType>,
+    pub operands: Vec<ValueType>,
+    pub write: Option<MemoryWrite>,
+    // The built-in operation that ran, or None for custom opcodes
+    pub builtin: Option<Opcode>,
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut values = vec![self.opcode];
+        values.extend(&self.parameters);
+
+        let text = disasm::instruction(&values, 0)
+            .map(|line| line.text)
+            .unwrap_or_else(|| format!("DATA {}", self.opcode));
+
+        let operands = self
+            .operands
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>();
+
+        write!(
+            f,
+            "{:>5}: {:<32} rb={:<6} ({})",
+            self.ip,
+            text,
+            self.relative_base,
+            operands.join(", ")
+        )?;
+
+        if let Some(write) = self.write {
+            write!(f, " [{}] {} -> {}", write.address, write.old, write.new)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub trait Tracer: Send {
+    fn trace(&mut self, trace: &Trace);
+
+    fn fault(&mut self, _error: &ExecutionError) {}
+
+    fn reset(&mut self) {}
+
+    fn receive(&mut self, _value: ValueType) {}
+
+    fn send(&mut self, _value: ValueType) {}
+}
+
+impl<T: Tracer> Tracer for Arc<Mutex<T>> {
+    fn trace(&mut self, trace: &Trace) {
+        self.lock().unwrap().trace(trace);
+    }
+
+    fn fault(&mut self, error: &ExecutionError) {
+        self.lock().unwrap().fault(error);
+    }
+
+    fn reset(&mut self) {
+        self.lock().unwrap().reset();
+    }
+
+    fn receive(&mut self, value: ValueType) {
+        self.lock().unwrap().receive(value);
+    }
+
+    fn send(&mut self, value: ValueType) {
+        self.lock().unwrap().send(value);
+    }
+}
+
+pub struct LogTracer<W: Write + Send> {
+    writer: W,
+}
+
+impl<W: Write + Send> LogTracer<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write + Send> Tracer for LogTracer<W> {
+    fn trace(&mut self, trace: &Trace) {
+        writeln!(self.writer, "{}", trace).unwrap_or(());
+    }
+
+    fn fault(&mut self, error: &ExecutionError) {
+        writeln!(self.writer, "error: {}", error).unwrap_or(());
+    }
+}
+
+// Each trace is a sequence of zigzag LEB128 varints: ip, relative base,
+// opcode, parameter count, the parameters, the resolved operands, and then
+// either 0 or 1 followed by the address, old and new value of the write
+fn write_varint(writer: &mut impl Write, value: ValueType) -> io::Result<()> {
+    let mut value = ((value << 1) ^ (value >> (ValueType::BITS - 1))) as u64;
+
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint(reader: &mut impl Read) -> io::Result<ValueType> {
+    let mut value = 0u64;
+    let mut shift = 0;
+
+    loop {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+
+        value |= ((byte[0] & 0x7f) as u64) << shift;
+        shift += 7;
+
+        if byte[0] & 0x80 == 0 {
+            return Ok((value >> 1) as ValueType ^ -((value & 1) as ValueType));
+        }
+
+        if shift >= 64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "varint too long",
+            ));
+        }
+    }
+}
+
+fn encode(writer: &mut impl Write, trace: &Trace) -> io::Result<()> {
+    write_varint(writer, trace.ip as ValueType)?;
+    write_varint(writer, trace.relative_base)?;
+    write_varint(writer, trace.opcode)?;
+    write_varint(writer, trace.parameters.len() as ValueType)?;
+
+    for &value in trace.parameters.iter().chain(trace.operands.iter()) {
+        write_varint(writer, value)?;
+    }
+
+    match trace.write {
+        Some(write) => {
+            write_varint(writer, 1)?;
+            write_varint(writer, write.address as ValueType)?;
+            write_varint(writer, write.old)?;
+            write_varint(writer, write.new)
+        }
+        None => write_varint(writer, 0),
+    }
+}
+
+fn decode(reader: &mut impl Read) -> io::Result<Trace> {
+    let ip = read_varint(reader)? as usize;
+    let relative_base = read_varint(reader)?;
+    let opcode = read_varint(reader)?;
+    let count = read_varint(reader)? as usize;
+
+    let parameters = (0..count)
+        .map(|_| read_varint(reader))
+        .collect::<io::Result<Vec<_>>>()?;
+    let operands = (0..count)
+        .map(|_| read_varint(reader))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let write = if read_varint(reader)? == 1 {
+        Some(MemoryWrite {
+            address: read_varint(reader)? as usize,
+            old: read_varint(reader)?,
+            new: read_varint(reader)?,
+        })
+    } else {
+        None
+    };
+
+    // Binary traces do not record the instruction set, so assume the default
+    let builtin = Opcode::parse(opcode)
+        .ok()
+        .filter(|instruction| instruction.len() == 1 + count)
+        .and_then(|instruction| instruction.opcode());
+
+    Ok(Trace {
+        ip,
+        relative_base,
+        opcode,
+        parameters,
+        operands,
+        write,
+        builtin,
+    })
+}
+
+pub fn read_binary_trace(mut reader: impl Read) -> io::Result<Vec<Trace>> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+
+    let mut remaining = &bytes[..];
+    let mut traces = vec![];
+
+    while !remaining.is_empty() {
+        traces.push(decode(&mut remaining)?);
+    }
+
+    Ok(traces)
+}
+
+pub struct BinaryTracer<W: Write + Send> {
+    writer: W,
+}
+
+impl<W: Write + Send> BinaryTracer<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write + Send> Tracer for BinaryTracer<W> {
+    fn trace(&mut self, trace: &Trace) {
+        encode(&mut self.writer, trace).unwrap_or(());
+    }
+
+    fn fault(&mut self, _error: &ExecutionError) {
+        self.writer.flush().unwrap_or(());
+    }
+}
+
+pub struct RingTracer<W: Write + Send> {
+    traces: VecDeque<Trace>,
+    capacity: usize,
+    writer: W,
+}
+
+impl RingTracer<io::Stderr> {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_writer(capacity, io::stderr())
+    }
+}
+
+impl<W: Write + Send> RingTracer<W> {
+    pub fn with_writer(capacity: usize, writer: W) -> Self {
+        Self {
+            traces: VecDeque::with_capacity(capacity),
+            capacity,
+            writer,
+        }
+    }
+
+    pub fn traces(&self) -> impl Iterator<Item = &Trace> {
+        self.traces.iter()
+    }
+}
+
+impl<W: Write + Send> Tracer for RingTracer<W> {
+    fn trace(&mut self, trace: &Trace) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.traces.len() == self.capacity {
+            self.traces.pop_front();
+        }
+
+        self.traces.push_back(trace.clone());
+    }
+
+    fn fault(&mut self, error: &ExecutionError) {
+        writeln!(
+            self.writer,
+            "error: {}, last {} instructions:",
+            error,
+            self.traces.len()
+        )
+        .unwrap_or(());
+
+        for trace in &self.traces {
+            writeln!(self.writer, "{}", trace).unwrap_or(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Computer;
+
+    #[derive(Clone)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuffer {
+        fn new() -> Self {
+            SharedBuffer(Arc::new(Mutex::new(vec![])))
+        }
+
+        fn contents(&self) -> Vec<u8> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn log() {
+        let buffer = SharedBuffer::new();
+        let mut computer = Computer::new("1002,4,3,4,33").unwrap();
+
+        computer.attach_tracer(Box::new(LogTracer::new(buffer.clone())));
+        computer.run().unwrap();
+
+        assert_eq!(
+            "    0: MUL [4], #3, [4]                 rb=0      (33, 3, 4) [4] 33 -> 99\n    \
+             4: HLT                              rb=0      ()\n",
+            String::from_utf8(buffer.contents()).unwrap()
+        );
+    }
+
+    #[test]
+    fn binary() {
+        let buffer = SharedBuffer::new();
+        let mut computer = Computer::new("109,-3,21101,-70,5,7,204,7,99").unwrap();
+
+        computer.attach_tracer(Box::new(BinaryTracer::new(buffer.clone())));
+        computer.run().unwrap();
+
+        let traces = read_binary_trace(&buffer.contents()[..]).unwrap();
+
+        assert_eq!(
+            vec![
+                Trace {
+                    ip: 0,
+                    relative_base: 0,
+                    opcode: 109,
+                    parameters: vec![-3],
+                    operands: vec![-3],
+                    write: None,
+                    builtin: Some(Opcode::AdjustRelativeBase),
+                },
+                Trace {
+                    ip: 2,
+                    relative_base: -3,
+                    opcode: 21101,
+                    parameters: vec![-70, 5, 7],
+                    operands: vec![-70, 5, 4],
+                    write: Some(MemoryWrite {
+                        address: 4,
+                        old: 5,
+                        new: -65,
+                    }),
+                    builtin: Some(Opcode::Add),
+                },
+                Trace {
+                    ip: 6,
+                    relative_base: -3,
+                    opcode: 204,
+                    parameters: vec![7],
+                    operands: vec![-65],
+                    write: None,
+                    builtin: Some(Opcode::Output),
+                },
+                Trace {
+                    ip: 8,
+                    relative_base: -3,
+                    opcode: 99,
+                    parameters: vec![],
+                    operands: vec![],
+                    write: None,
+                    builtin: Some(Opcode::Halt),
+                },
+            ],
+            traces
+        );
+    }
+
+    #[test]
+    fn ring_buffer_dumped_on_error() {
+        let buffer = SharedBuffer::new();
+        let mut computer = Computer::new("1101,1,1,9,1101,2,2,9,98,0").unwrap();
+
+        computer.attach_tracer(Box::new(RingTracer::with_writer(1, buffer.clone())));
+
+        assert!(computer.run().is_err());
+        assert_eq!(
+            "error: invalid opcode 98 at ip 8 (relative base 0), last 1 instructions:\n    \
+             4: ADD #2, #2, [9]                  rb=0      (2, 2, 9) [9] 2 -> 4\n",
+            String::from_utf8(buffer.contents()).unwrap()
+        );
+    }
+
+    #[test]
+    fn every_fault_reported_once() {
+        #[derive(Clone)]
+        struct Faults(Arc<Mutex<Vec<String>>>);
+
+        impl Tracer for Faults {
+            fn trace(&mut self, _trace: &Trace) {}
+
+            fn fault(&mut self, error: &ExecutionError) {
+                self.0.lock().unwrap().push(error.to_string());
+            }
+        }
+
+        let faults = Faults(Arc::new(Mutex::new(vec![])));
+        let mut computer = Computer::new("3,0,1105,1,2").unwrap().instruction_budget(3);
+
+        computer.attach_tracer(Box::new(faults.clone()));
+
+        let (sender, _receiver) = computer.get_io();
+
+        drop(sender);
+
+        let disconnected = computer.run().unwrap_err().to_string();
+
+        computer.provide_input(1);
+        *computer.dma(2).unwrap() = 98;
+
+        let invalid = computer.run_until_output().unwrap_err().to_string();
+        let stepped = computer.step().unwrap_err().to_string();
+
+        *computer.dma(2).unwrap() = 1105;
+
+        let exhausted = computer.run_until_output().unwrap_err().to_string();
+
+        assert_eq!(
+            vec![disconnected, invalid, stepped, exhausted],
+            *faults.0.lock().unwrap()
+        );
+    }
+}